@@ -1,10 +1,27 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
-use crate::config::Config;
+use crate::config::{
+    format_time, unix_time, Config, Profile, SavedNode, Subscription, DEFAULT_EXPIRY_WARN_DAYS,
+    DEFAULT_QUOTA_WARN_PERCENT,
+};
+use crate::export::{expand_home, qr_lines, write_subscription};
+use crate::history::{History, HistoryEntry, DEFAULT_HISTORY_SIZE};
 use crate::latency::{LatencyResult, TestType};
-use crate::vmess::{LatencyStatus, VmessNode};
-use crate::xray::{find_active_node_index, read_active_node};
+use crate::merge::{merge_nodes, DiffSummary};
+use crate::node::{LatencyStatus, Node};
+use crate::notice::InfoRules;
+use crate::serve::{ServeOptions, SharedNodes};
+use crate::service::ServiceBackend;
+use crate::source::{LocalSource, SourceStamp};
+use crate::subscribe::{
+    link_prefix, load_cached, FetchMode, FetchOutcome, FetchTarget, ParseReport, RequestOptions,
+};
+use crate::xray::{check_supported, find_active_node_index, read_active_node};
 
 /// Sort column options
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -84,16 +101,243 @@ impl SortDirection {
 /// Node with original index for sorting
 #[derive(Clone)]
 pub struct IndexedNode {
-    pub node: VmessNode,
+    pub node: Node,
     pub original_index: usize,
+    /// Index of the subscription the node came from
+    pub source: usize,
+}
+
+/// What a text input form edits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormTarget {
+    /// Add a subscription (None) or edit the one at the given index
+    Subscription(Option<usize>),
+    /// Add nodes to the manual group from share links
+    ManualNodes,
+    /// Write the exported links to a file as a base64 subscription
+    ExportFile,
+}
+
+/// A labelled text field of an input form
+#[derive(Debug, Clone)]
+pub struct FormField {
+    pub label: &'static str,
+    pub value: String,
+}
+
+/// Multi-field text input dialog
+#[derive(Debug, Clone)]
+pub struct InputForm {
+    pub target: FormTarget,
+    pub fields: Vec<FormField>,
+    /// Index of the field receiving keystrokes
+    pub focus: usize,
+}
+
+impl InputForm {
+    /// Form for adding or editing a subscription
+    fn subscription(index: Option<usize>, subscription: Option<&Subscription>) -> Self {
+        let (name, url, via, request) = subscription
+            .map(|s| {
+                (
+                    s.name.clone(),
+                    s.url.clone(),
+                    FetchMode::format_list(&s.fetch_via),
+                    s.request.clone(),
+                )
+            })
+            .unwrap_or_default();
+        let field = |label, value| FormField { label, value };
+        InputForm {
+            target: FormTarget::Subscription(index),
+            fields: vec![
+                field("Name", name),
+                field("URL", url),
+                field("Fetch via", via),
+                field("User-Agent", request.user_agent.unwrap_or_default()),
+                field("Headers", RequestOptions::format_headers(&request.headers)),
+                field(
+                    "Basic auth",
+                    RequestOptions::format_basic_auth(request.basic_auth.as_ref()),
+                ),
+                field(
+                    "Timeout (s)",
+                    request
+                        .timeout_secs
+                        .map(|t| t.to_string())
+                        .unwrap_or_default(),
+                ),
+                field(
+                    "Refresh (min)",
+                    subscription
+                        .and_then(|s| s.refresh_interval_mins)
+                        .map(|m| m.to_string())
+                        .unwrap_or_default(),
+                ),
+                field(
+                    "Watch (y/n)",
+                    if subscription.is_some_and(|s| s.watch) {
+                        "y".to_string()
+                    } else {
+                        String::new()
+                    },
+                ),
+            ],
+            focus: 0,
+        }
+    }
+
+    /// Form for pasting share links into the manual group
+    fn manual_nodes() -> Self {
+        InputForm {
+            target: FormTarget::ManualNodes,
+            fields: vec![FormField {
+                label: "Links",
+                value: String::new(),
+            }],
+            focus: 0,
+        }
+    }
+
+    /// Form asking where to write an exported subscription
+    fn export_file() -> Self {
+        InputForm {
+            target: FormTarget::ExportFile,
+            fields: vec![FormField {
+                label: "Path",
+                value: "~/subman-export.txt".to_string(),
+            }],
+            focus: 0,
+        }
+    }
+
+    /// Dialog title
+    pub fn title(&self) -> &'static str {
+        match self.target {
+            FormTarget::Subscription(None) => "Add Subscription",
+            FormTarget::Subscription(Some(_)) => "Edit Subscription",
+            FormTarget::ManualNodes => "Add Nodes",
+            FormTarget::ExportFile => "Export Subscription",
+        }
+    }
+
+    /// Trimmed value of a field
+    fn value(&self, index: usize) -> String {
+        self.fields[index].value.trim().to_string()
+    }
+
+    /// Request options from the User-Agent, Headers, Basic auth and Timeout fields
+    fn request_options(&self) -> Result<RequestOptions> {
+        let user_agent = self.value(3);
+        Ok(RequestOptions {
+            user_agent: (!user_agent.is_empty()).then_some(user_agent),
+            headers: RequestOptions::parse_headers(&self.value(4))?,
+            basic_auth: RequestOptions::parse_basic_auth(&self.value(5)),
+            timeout_secs: self.positive_number(6)?,
+        })
+    }
+
+    /// Optional positive whole number in a field, None when empty
+    fn positive_number(&self, index: usize) -> Result<Option<u64>> {
+        let value = self.value(index);
+        if value.is_empty() {
+            return Ok(None);
+        }
+        match value.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => Err(anyhow!(
+                "{} must be a positive whole number",
+                self.fields[index].label
+            )),
+        }
+    }
+
+    /// Yes/no field, empty meaning no
+    fn yes_no(&self, index: usize) -> Result<bool> {
+        match self.value(index).to_ascii_lowercase().as_str() {
+            "" | "n" | "no" => Ok(false),
+            "y" | "yes" => Ok(true),
+            _ => Err(anyhow!("{} must be y or n", self.fields[index].label)),
+        }
+    }
+
+    /// Type a character into the focused field
+    pub fn push(&mut self, c: char) {
+        self.fields[self.focus].value.push(c);
+    }
+
+    /// Paste text into the focused field, line breaks becoming spaces
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace(['\r', '\n'], " ");
+        self.fields[self.focus].value.push_str(&text);
+    }
+
+    /// Delete the last character of the focused field
+    pub fn pop(&mut self) {
+        self.fields[self.focus].value.pop();
+    }
+
+    /// Move focus to the next field, wrapping around
+    pub fn focus_next(&mut self) {
+        self.focus = (self.focus + 1) % self.fields.len();
+    }
+
+    /// Move focus to the previous field, wrapping around
+    pub fn focus_previous(&mut self) {
+        self.focus = (self.focus + self.fields.len() - 1) % self.fields.len();
+    }
+}
+
+/// Share links of exported nodes, one shown as a QR code at a time
+#[derive(Debug, Clone)]
+pub struct ExportView {
+    /// Display name and share link of each exported node
+    pub links: Vec<(String, String)>,
+    /// Index of the link shown as a QR code
+    pub current: usize,
+    /// QR code of the current link, one string per terminal row
+    pub qr: Vec<String>,
+}
+
+/// Source index of nodes in the manual group
+pub const MANUAL_SOURCE: usize = usize::MAX;
+/// Display name of the manual group
+pub const MANUAL_GROUP: &str = "manual";
+
+/// Flatten the nodes of enabled subscriptions followed by the manual group,
+/// returning each node's subscription index or `MANUAL_SOURCE`
+fn flatten_subscriptions(
+    subscriptions: &[Subscription],
+    manual_nodes: &[SavedNode],
+) -> (Vec<Node>, Vec<usize>) {
+    let mut nodes = Vec::new();
+    let mut sources = Vec::new();
+    for (i, subscription) in subscriptions.iter().enumerate() {
+        if !subscription.enabled {
+            continue;
+        }
+        for node in subscription.to_nodes() {
+            nodes.push(node);
+            sources.push(i);
+        }
+    }
+    for saved in manual_nodes {
+        nodes.push(Node::from(saved.clone()));
+        sources.push(MANUAL_SOURCE);
+    }
+    (nodes, sources)
 }
 
 /// Application state
 pub struct App {
-    /// Subscription URL
-    pub subscribe_url: Option<String>,
-    /// List of vmess nodes (original order)
-    nodes: Vec<VmessNode>,
+    /// Configured subscriptions, in display order
+    pub subscriptions: Vec<Subscription>,
+    /// Nodes added by hand, kept across refreshes
+    manual_nodes: Vec<SavedNode>,
+    /// List of nodes from enabled subscriptions (original order)
+    nodes: Vec<Node>,
+    /// Subscription index of each entry in `nodes`
+    node_sources: Vec<usize>,
     /// Sorted view of nodes with original indices
     pub sorted_nodes: Vec<IndexedNode>,
     /// Currently selected index in sorted view
@@ -108,32 +352,128 @@ pub struct App {
     pub testing: bool,
     /// Whether subscription refresh is in progress
     pub refreshing: bool,
-    /// Whether in URL input mode
-    pub input_mode: bool,
-    /// Input buffer for URL
-    pub input_buffer: String,
+    /// Open text input form, if any
+    pub input: Option<InputForm>,
+    /// Whether the subscription manager screen is open
+    pub show_subscriptions: bool,
+    /// Selected row in the subscription manager
+    pub subscription_selected: usize,
+    /// Subscription waiting for the user to confirm its removal
+    pub pending_removal: Option<usize>,
     /// Current test type being performed
     pub current_test_type: Option<TestType>,
     /// Error message to display in popup
     pub error_message: Option<String>,
+    /// Parse reports from the last refresh, one per fetched subscription
+    pub parse_reports: Vec<(String, ParseReport)>,
+    /// Whether the parse report popup is open
+    pub show_report: bool,
+    /// Original indices of nodes marked for export
+    pub marked: HashSet<usize>,
+    /// Open export popup, if any
+    pub export: Option<ExportView>,
+    /// Scroll offset of the parse report popup
+    pub report_scroll: u16,
+    /// Recently applied xray configs, newest first
+    pub history: History,
+    /// Number of applied configs to keep
+    pub history_size: usize,
+    /// Whether the history popup is open
+    pub show_history: bool,
+    /// Selected entry in the history popup
+    pub history_selected: usize,
     /// Cancel flag for latency testing
     pub cancel_flag: Arc<AtomicBool>,
     /// Parallel test count
     pub parallel_count: usize,
+    /// subman config file, None when the config directory is unknown
+    config_path: Option<PathBuf>,
     /// Xray config file path
     pub xray_config_path: String,
+    /// Tag of the outbound patched in the existing xray config, None to overwrite it
+    pub xray_outbound_tag: Option<String>,
+    /// Controller restarting xray after its config changed
+    pub service: ServiceBackend,
+    /// Configured xray setups
+    pub profiles: Vec<Profile>,
+    /// Index of the selected profile, None for the built-in config
+    pub active_profile: Option<usize>,
     /// Current sort column
     pub sort_column: SortColumn,
     /// Current sort direction
     pub sort_direction: SortDirection,
+    /// Quota warning threshold in percent
+    pub quota_warn_percent: u64,
+    /// Expiry warning threshold in days
+    pub expiry_warn_days: u64,
+    /// Rules moving informational pseudo-nodes into subscription notices
+    pub info_rules: InfoRules,
+    /// Whether to TCP-test new nodes after an automatic refresh
+    pub retest_after_refresh: bool,
+    /// Unix timestamp of the last automatic refresh attempt, by subscription URL
+    /// so renaming a subscription keeps it
+    refresh_attempts: HashMap<String, u64>,
+    /// Last seen state of watched file sources, by subscription URL
+    source_stamps: HashMap<String, SourceStamp>,
+    /// Unix timestamp of the last check of watched sources
+    watch_checked_at: u64,
+    /// Subscription server settings from the config
+    pub serve_options: Option<ServeOptions>,
+    /// Node list published to the subscription server
+    pub served_nodes: SharedNodes,
 }
 
 impl App {
     /// Create a new App instance, loading config from file
-    pub fn new(parallel_count: usize, xray_config_path: String) -> Self {
-        let config = Config::load();
-        let nodes = config.to_vmess_nodes();
-        let has_url = config.subscribe_url.is_some();
+    pub fn new(
+        parallel_count: usize,
+        xray_config_path: String,
+        xray_outbound_tag: Option<String>,
+    ) -> Self {
+        Self::with_config_path(
+            Config::config_path(),
+            parallel_count,
+            xray_config_path,
+            xray_outbound_tag,
+        )
+    }
+
+    /// Create a new App instance reading and saving the given config file
+    pub fn with_config_path(
+        config_path: Option<PathBuf>,
+        parallel_count: usize,
+        xray_config_path: String,
+        xray_outbound_tag: Option<String>,
+    ) -> Self {
+        // Start empty if the config is broken; saving stays refused until it is fixed
+        let (mut config, load_error) = match Config::load(config_path.as_deref()) {
+            Ok(config) => (config, None),
+            Err(e) => (
+                Config::default(),
+                Some(format!("{e}\nChanges will not be saved until it is fixed.")),
+            ),
+        };
+        let xray_outbound_tag = xray_outbound_tag.or(config.xray_outbound_tag.take());
+        // Fall back to built-in keywords if the configured rules don't compile
+        let (info_rules, error_message) = match &config.info_rules {
+            Some(rules) => match InfoRules::compile(rules) {
+                Ok(compiled) => (compiled, load_error),
+                Err(e) => (InfoRules::defaults(), Some(e.to_string())),
+            },
+            None => (InfoRules::defaults(), load_error),
+        };
+
+        // Offline startup: parse the cached body of subscriptions without cached nodes
+        for subscription in &mut config.subscriptions {
+            if subscription.nodes.is_empty() {
+                if let Some((nodes, _)) = load_cached(&subscription.url, &info_rules) {
+                    subscription.update_nodes(&nodes);
+                }
+            }
+        }
+
+        let (nodes, node_sources) = flatten_subscriptions(&config.subscriptions, &config.manual_nodes);
+        let has_url = !config.subscriptions.is_empty();
         let has_nodes = !nodes.is_empty();
 
         // Load sort settings
@@ -141,7 +481,7 @@ impl App {
         let sort_direction = SortDirection::from_str(config.sort_direction.as_deref());
 
         // Find active node from xray config
-        let active_node_index = read_active_node(&xray_config_path)
+        let active_node_index = read_active_node(&xray_config_path, xray_outbound_tag.as_deref())
             .and_then(|active| find_active_node_index(&nodes, &active));
 
         let status = if has_url && has_nodes {
@@ -150,11 +490,13 @@ impl App {
                 nodes.len()
             )
         } else if has_url {
-            "Press R to refresh subscription".to_string()
+            "Press R to refresh subscriptions".to_string()
         } else {
-            "Press U to set subscription URL".to_string()
+            "Press U to add a subscription".to_string()
         };
 
+        let served_nodes = nodes.clone();
+
         // Create sorted view
         let mut sorted_nodes: Vec<IndexedNode> = nodes
             .iter()
@@ -162,6 +504,7 @@ impl App {
             .map(|(i, n)| IndexedNode {
                 node: n.clone(),
                 original_index: i,
+                source: node_sources[i],
             })
             .collect();
 
@@ -174,8 +517,10 @@ impl App {
             .unwrap_or(0);
 
         Self {
-            subscribe_url: config.subscribe_url,
+            subscriptions: config.subscriptions,
+            manual_nodes: config.manual_nodes,
             nodes,
+            node_sources,
             sorted_nodes,
             selected,
             active_node_index,
@@ -183,15 +528,44 @@ impl App {
             should_quit: false,
             testing: false,
             refreshing: false,
-            input_mode: false,
-            input_buffer: String::new(),
+            input: None,
+            show_subscriptions: false,
+            subscription_selected: 0,
+            pending_removal: None,
             current_test_type: None,
-            error_message: None,
+            error_message,
+            parse_reports: Vec::new(),
+            show_report: false,
+            report_scroll: 0,
+            history: History::load(&xray_config_path),
+            history_size: config.history_size.unwrap_or(DEFAULT_HISTORY_SIZE),
+            show_history: false,
+            history_selected: 0,
+            marked: HashSet::new(),
+            export: None,
             cancel_flag: Arc::new(AtomicBool::new(false)),
             parallel_count,
+            config_path,
             xray_config_path,
+            xray_outbound_tag,
+            service: config.service.unwrap_or_default(),
+            active_profile: config
+                .active_profile
+                .and_then(|name| config.profiles.iter().position(|p| p.name == name)),
+            profiles: config.profiles,
             sort_column,
             sort_direction,
+            quota_warn_percent: config
+                .quota_warn_percent
+                .unwrap_or(DEFAULT_QUOTA_WARN_PERCENT),
+            expiry_warn_days: config.expiry_warn_days.unwrap_or(DEFAULT_EXPIRY_WARN_DAYS),
+            info_rules,
+            retest_after_refresh: config.retest_after_refresh.unwrap_or(false),
+            refresh_attempts: HashMap::new(),
+            source_stamps: HashMap::new(),
+            watch_checked_at: 0,
+            serve_options: config.serve,
+            served_nodes: Arc::new(RwLock::new(served_nodes)),
         }
     }
 
@@ -204,6 +578,7 @@ impl App {
             .map(|(i, n)| IndexedNode {
                 node: n.clone(),
                 original_index: i,
+                source: self.node_sources[i],
             })
             .collect();
         self.apply_sort();
@@ -235,6 +610,56 @@ impl App {
         self.save_sort_config();
     }
 
+    /// Name of the selected profile
+    pub fn active_profile_name(&self) -> Option<&str> {
+        self.active_profile
+            .and_then(|i| self.profiles.get(i))
+            .map(|p| p.name.as_str())
+    }
+
+    /// Template file of the selected profile, if it has one
+    pub fn active_template(&self) -> Option<PathBuf> {
+        self.active_profile
+            .and_then(|i| self.profiles.get(i))
+            .and_then(|p| p.template.as_deref())
+            .map(expand_home)
+    }
+
+    /// Select a profile by name
+    pub fn select_profile(&mut self, name: &str) -> Result<()> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| anyhow!("Unknown profile \"{name}\""))?;
+        self.active_profile = Some(index);
+        Ok(())
+    }
+
+    /// Cycle through the profiles and the built-in config
+    pub fn cycle_profile(&mut self) {
+        if self.profiles.is_empty() {
+            self.set_status("No profiles configured");
+            return;
+        }
+        self.active_profile = match self.active_profile {
+            None => Some(0),
+            Some(i) if i + 1 < self.profiles.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        let status = match (self.active_profile_name(), self.active_template()) {
+            (Some(name), Some(template)) => {
+                format!("Profile: {name} (template {})", template.display())
+            }
+            (Some(name), None) => format!("Profile: {name}"),
+            (None, _) => "Profile: built-in config".to_string(),
+        };
+        self.set_status(status);
+
+        let active_profile = self.active_profile_name().map(String::from);
+        self.update_config("profile", |config| config.active_profile = active_profile);
+    }
+
     /// Clamp selection to valid range
     fn clamp_selection(&mut self) {
         if self.sorted_nodes.is_empty() {
@@ -259,7 +684,7 @@ impl App {
     }
 
     /// Get the currently selected node
-    pub fn selected_node(&self) -> Option<&VmessNode> {
+    pub fn selected_node(&self) -> Option<&Node> {
         self.sorted_nodes.get(self.selected).map(|n| &n.node)
     }
 
@@ -272,10 +697,7 @@ impl App {
     pub fn update_latency(&mut self, result: LatencyResult) {
         // Update in original nodes
         if let Some(node) = self.nodes.get_mut(result.index) {
-            match result.test_type {
-                TestType::Http => node.http_latency = result.latency,
-                TestType::Tcp => node.tcp_latency = result.latency,
-            }
+            node.set_latency(result.test_type, result.latency);
         }
         // Update in sorted view
         if let Some(indexed) = self
@@ -283,39 +705,299 @@ impl App {
             .iter_mut()
             .find(|n| n.original_index == result.index)
         {
-            match result.test_type {
-                TestType::Http => indexed.node.http_latency = result.latency,
-                TestType::Tcp => indexed.node.tcp_latency = result.latency,
+            indexed.node.set_latency(result.test_type, result.latency);
+        }
+        // Update in the served list
+        let mut served = self.served_nodes.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = served.get_mut(result.index) {
+            node.set_latency(result.test_type, result.latency);
+        }
+    }
+
+    /// Replace the node list published to the subscription server
+    fn publish_nodes(&self) {
+        *self.served_nodes.write().unwrap_or_else(|e| e.into_inner()) = self.nodes.clone();
+    }
+
+    /// Get the name of the subscription a node came from
+    pub fn source_name(&self, source: usize) -> &str {
+        if source == MANUAL_SOURCE {
+            return MANUAL_GROUP;
+        }
+        self.subscriptions
+            .get(source)
+            .map_or("", |s| s.name.as_str())
+    }
+
+    /// Quota summary of the subscription the selected node came from
+    pub fn selected_userinfo_summary(&self) -> Option<String> {
+        let source = self.sorted_nodes.get(self.selected)?.source;
+        let subscription = self.subscriptions.get(source)?;
+        let userinfo = subscription.userinfo?;
+        Some(format!(
+            "{}: {}",
+            subscription.name,
+            userinfo.summary(unix_time())
+        ))
+    }
+
+    /// Notices of the subscription the selected node came from
+    pub fn selected_notices(&self) -> &[String] {
+        self.sorted_nodes
+            .get(self.selected)
+            .and_then(|n| self.subscriptions.get(n.source))
+            .map_or(&[], |s| s.notices.as_slice())
+    }
+
+    /// Quota and expiry warnings of enabled subscriptions
+    pub fn subscription_warnings(&self) -> Vec<String> {
+        let now = unix_time();
+        self.subscriptions
+            .iter()
+            .filter(|s| s.enabled)
+            .filter_map(|s| {
+                let mut warnings = s
+                    .userinfo
+                    .map(|u| u.warnings(now, self.quota_warn_percent, self.expiry_warn_days))
+                    .unwrap_or_default();
+                if let Some(since) = s.stale_since {
+                    warnings.push(format!("stale since {}", format_time(since)));
+                }
+                (!warnings.is_empty()).then(|| format!("{}: {}", s.name, warnings.join(", ")))
+            })
+            .collect()
+    }
+
+    /// Copy current nodes (with latencies) back into their subscriptions
+    fn store_nodes(&mut self) {
+        for (i, subscription) in self.subscriptions.iter_mut().enumerate() {
+            if subscription.enabled {
+                subscription.update_nodes(
+                    self.nodes
+                        .iter()
+                        .zip(&self.node_sources)
+                        .filter(|(_, source)| **source == i)
+                        .map(|(node, _)| node),
+                );
             }
         }
+        self.manual_nodes = self
+            .nodes
+            .iter()
+            .zip(&self.node_sources)
+            .filter(|(_, source)| **source == MANUAL_SOURCE)
+            .map(|(node, _)| SavedNode::from(node))
+            .collect();
     }
 
-    /// Set nodes from subscription
-    pub fn set_nodes(&mut self, nodes: Vec<VmessNode>) {
+    /// Rebuild the node list from enabled subscriptions and the manual group
+    fn reload_nodes(&mut self) {
+        self.forget_removed_sources();
+        let selected_fingerprint = self.selected_node().map(Node::fingerprint);
+        let (nodes, node_sources) = flatten_subscriptions(&self.subscriptions, &self.manual_nodes);
+
         // Try to find active node in the new list
-        let active_node_index = read_active_node(&self.xray_config_path)
-            .and_then(|active| find_active_node_index(&nodes, &active));
+        let active_node_index =
+            read_active_node(&self.xray_config_path, self.xray_outbound_tag.as_deref())
+                .and_then(|active| find_active_node_index(&nodes, &active));
 
         self.nodes = nodes;
+        self.node_sources = node_sources;
         self.active_node_index = active_node_index;
+        self.marked.clear();
         self.rebuild_sorted_view();
+        self.publish_nodes();
 
-        // Find selected index in sorted view for active node
-        self.selected = active_node_index
-            .and_then(|ai| {
+        // Keep the previously selected node selected, else select the active node
+        self.selected = selected_fingerprint
+            .and_then(|fp| {
                 self.sorted_nodes
                     .iter()
-                    .position(|n| n.original_index == ai)
+                    .position(|n| n.node.fingerprint() == fp)
+            })
+            .or_else(|| {
+                active_node_index.and_then(|ai| {
+                    self.sorted_nodes
+                        .iter()
+                        .position(|n| n.original_index == ai)
+                })
             })
             .unwrap_or(0);
     }
 
-    /// Clear all nodes
-    pub fn clear_nodes(&mut self) {
-        self.nodes.clear();
-        self.sorted_nodes.clear();
-        self.selected = 0;
-        self.active_node_index = None;
+    /// Drop refresh and watch state of URLs no subscription uses any more
+    fn forget_removed_sources(&mut self) {
+        let urls: HashSet<&str> = self.subscriptions.iter().map(|s| s.url.as_str()).collect();
+        self.refresh_attempts
+            .retain(|url, _| urls.contains(url.as_str()));
+        self.source_stamps
+            .retain(|url, _| urls.contains(url.as_str()));
+    }
+
+    /// URLs of subscriptions to refresh: the one at `only`, or every enabled one
+    pub fn refresh_targets(&self, only: Option<usize>) -> Vec<FetchTarget> {
+        self.subscriptions
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| match only {
+                Some(index) => *i == index,
+                None => s.enabled,
+            })
+            .map(|(index, s)| FetchTarget {
+                index,
+                url: s.url.clone(),
+                via: s.fetch_via.clone(),
+                request: s.request.clone(),
+            })
+            .collect()
+    }
+
+    /// Subscriptions whose refresh interval has elapsed, recording the attempt
+    /// so a failing subscription waits a full interval before retrying
+    pub fn due_refresh_targets(&mut self, now: u64) -> Vec<FetchTarget> {
+        let due: Vec<usize> = self
+            .subscriptions
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.next_refresh_at(self.refresh_attempts.get(&s.url).copied())
+                    .is_some_and(|at| at <= now)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        for &i in &due {
+            self.refresh_attempts
+                .insert(self.subscriptions[i].url.clone(), now);
+        }
+        due.into_iter()
+            .flat_map(|i| self.refresh_targets(Some(i)))
+            .collect()
+    }
+
+    /// Watched file sources that changed since they were last seen, checked at
+    /// most once a second; every watched source counts as changed on first check
+    pub fn changed_sources(&mut self, now: u64) -> Vec<FetchTarget> {
+        if now == self.watch_checked_at {
+            return Vec::new();
+        }
+        self.watch_checked_at = now;
+
+        let mut changed = Vec::new();
+        for (i, subscription) in self.subscriptions.iter().enumerate() {
+            if !subscription.enabled || !subscription.watch {
+                continue;
+            }
+            let Some(stamp) = LocalSource::parse(&subscription.url).and_then(|s| s.stamp()) else {
+                continue;
+            };
+            if self.source_stamps.get(&subscription.url) != Some(&stamp) {
+                self.source_stamps.insert(subscription.url.clone(), stamp);
+                changed.push(i);
+            }
+        }
+        changed
+            .into_iter()
+            .flat_map(|i| self.refresh_targets(Some(i)))
+            .collect()
+    }
+
+    /// Seconds until the next automatic refresh, None when no subscription has an interval
+    pub fn next_auto_refresh(&self, now: u64) -> Option<u64> {
+        self.subscriptions
+            .iter()
+            .filter_map(|s| s.next_refresh_at(self.refresh_attempts.get(&s.url).copied()))
+            .min()
+            .map(|at| at.saturating_sub(now))
+    }
+
+    /// Unix timestamp of the most recent successful fetch of an enabled subscription
+    pub fn last_refreshed(&self) -> Option<u64> {
+        self.subscriptions
+            .iter()
+            .filter(|s| s.enabled)
+            .filter_map(|s| s.fetched_at)
+            .max()
+    }
+
+    /// Merge fetched nodes into each subscription, keeping latency results of
+    /// unchanged servers; failed subscriptions keep their cached nodes
+    pub fn apply_refresh(&mut self, outcomes: Vec<FetchOutcome>) {
+        self.store_nodes();
+        self.parse_reports.clear();
+
+        let total = outcomes.len();
+        let mut errors = Vec::new();
+        let mut stale = Vec::new();
+        let mut proxied = Vec::new();
+        let mut diff = DiffSummary::default();
+        for (index, outcome) in outcomes {
+            let Some(subscription) = self.subscriptions.get_mut(index) else {
+                continue;
+            };
+            match outcome {
+                Ok(fetched) => {
+                    let (merged, changes) = merge_nodes(subscription.to_nodes(), fetched.nodes);
+                    diff.add(changes);
+                    subscription.update_nodes(&merged);
+                    // A 304 response may omit the header; keep the last known quota then
+                    if fetched.userinfo.is_some() || !fetched.not_modified {
+                        subscription.userinfo = fetched.userinfo;
+                    }
+                    subscription.fetched_at = Some(unix_time());
+                    if fetched.via != FetchMode::Direct {
+                        proxied.push(format!("{} via {}", subscription.name, fetched.via));
+                    }
+                    subscription.fetched_via = Some(fetched.via);
+                    subscription.stale_since = None;
+                    subscription.notices = fetched.report.notices.clone();
+                    self.parse_reports
+                        .push((subscription.name.clone(), fetched.report));
+                }
+                Err(e) => {
+                    // Fall back to the last good copy instead of an empty list
+                    if subscription.nodes.is_empty() {
+                        if let Some((nodes, _)) = load_cached(&subscription.url, &self.info_rules) {
+                            subscription.update_nodes(&nodes);
+                        }
+                    }
+                    if subscription.nodes.is_empty() {
+                        errors.push(format!("{}: {e}", subscription.name));
+                    } else {
+                        stale.push(subscription.name.clone());
+                        subscription.stale_since.get_or_insert(unix_time());
+                    }
+                }
+            }
+        }
+
+        self.reload_nodes();
+        self.save_config();
+
+        let has_issues = self.parse_reports.iter().any(|(_, r)| !r.issues.is_empty());
+        let hint = if has_issues {
+            ". Press P for details"
+        } else {
+            ""
+        };
+        let stale_note = if stale.is_empty() {
+            String::new()
+        } else {
+            format!(", using cached copy of {}", stale.join(", "))
+        };
+        let via_note = if proxied.is_empty() {
+            String::new()
+        } else {
+            format!(", {}", proxied.join(", "))
+        };
+        self.set_status(format!(
+            "Refreshed {}/{total} subscriptions, {} nodes ({}){via_note}{stale_note}{hint}",
+            total - errors.len() - stale.len(),
+            self.nodes.len(),
+            diff.summary()
+        ));
+        if !errors.is_empty() {
+            self.set_error(errors.join("\n"));
+        }
     }
 
     /// Clear HTTP latencies
@@ -326,6 +1008,7 @@ impl App {
         for indexed in &mut self.sorted_nodes {
             indexed.node.http_latency = LatencyStatus::NotTested;
         }
+        self.publish_nodes();
     }
 
     /// Clear TCP latencies
@@ -336,6 +1019,7 @@ impl App {
         for indexed in &mut self.sorted_nodes {
             indexed.node.tcp_latency = LatencyStatus::NotTested;
         }
+        self.publish_nodes();
     }
 
     /// Set status message
@@ -353,57 +1037,433 @@ impl App {
         self.error_message = None;
     }
 
-    /// Enter URL input mode
-    pub fn enter_input_mode(&mut self) {
-        self.input_mode = true;
-        self.input_buffer = self.subscribe_url.clone().unwrap_or_default();
+    /// Mark or unmark the selected node for export and move to the next one
+    pub fn toggle_mark(&mut self) {
+        let Some(index) = self.selected_original_index() else {
+            return;
+        };
+        if !self.marked.remove(&index) {
+            self.marked.insert(index);
+        }
+        self.select_next();
     }
 
-    /// Exit URL input mode without saving
-    pub fn exit_input_mode(&mut self) {
-        self.input_mode = false;
-        self.input_buffer.clear();
+    /// Open the export popup for the marked nodes, or the selected one if none are marked
+    pub fn open_export(&mut self) {
+        let links: Vec<(String, String)> = self
+            .sorted_nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, n)| {
+                if self.marked.is_empty() {
+                    *i == self.selected
+                } else {
+                    self.marked.contains(&n.original_index)
+                }
+            })
+            .map(|(_, n)| (n.node.display_name(), n.node.to_link()))
+            .collect();
+        if links.is_empty() {
+            return;
+        }
+        self.export = Some(ExportView {
+            links,
+            current: 0,
+            qr: Vec::new(),
+        });
+        self.show_export_link(0);
     }
 
-    /// Confirm URL input and save
-    pub fn confirm_input(&mut self) {
-        let url = self.input_buffer.trim().to_string();
-        if !url.is_empty() {
-            self.subscribe_url = Some(url);
+    /// Show the QR code of another exported link
+    pub fn show_export_link(&mut self, index: usize) {
+        let Some(export) = self.export.as_mut() else {
+            return;
+        };
+        let Some((_, link)) = export.links.get(index) else {
+            return;
+        };
+        match qr_lines(link) {
+            Ok(qr) => {
+                export.current = index;
+                export.qr = qr;
+            }
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    /// Show the previous exported link, wrapping around
+    pub fn previous_export_link(&mut self) {
+        if let Some(export) = &self.export {
+            let len = export.links.len();
+            self.show_export_link((export.current + len - 1) % len);
+        }
+    }
+
+    /// Show the next exported link, wrapping around
+    pub fn next_export_link(&mut self) {
+        if let Some(export) = &self.export {
+            self.show_export_link((export.current + 1) % export.links.len());
+        }
+    }
+
+    /// Ask for a file to write the exported links to
+    pub fn export_to_file(&mut self) {
+        if self.export.is_some() {
+            self.input = Some(InputForm::export_file());
+        }
+    }
+
+    /// Close the export popup
+    pub fn close_export(&mut self) {
+        self.export = None;
+    }
+
+    /// Open the parse report popup if a report is available
+    pub fn open_report(&mut self) {
+        if !self.parse_reports.is_empty() {
+            self.show_report = true;
+            self.report_scroll = 0;
+        } else {
+            self.set_status("No parse report yet. Press R to refresh.");
+        }
+    }
+
+    /// Close the parse report popup
+    pub fn close_report(&mut self) {
+        self.show_report = false;
+    }
+
+    /// Scroll the parse report popup up
+    pub fn scroll_report_up(&mut self, lines: u16) {
+        self.report_scroll = self.report_scroll.saturating_sub(lines);
+    }
+
+    /// Scroll the parse report popup down, stopping at the last line
+    pub fn scroll_report_down(&mut self, lines: u16) {
+        // Each report renders a heading plus one line per issue (or a "no problems" line)
+        let total: usize = self
+            .parse_reports
+            .iter()
+            .map(|(_, r)| 1 + r.issues.len().max(1))
+            .sum();
+        let max = total.saturating_sub(1) as u16;
+        self.report_scroll = self.report_scroll.saturating_add(lines).min(max);
+    }
+
+    /// Open the subscription manager screen
+    pub fn open_subscriptions(&mut self) {
+        self.show_subscriptions = true;
+        self.clamp_subscription_selection();
+    }
+
+    /// Close the subscription manager screen
+    pub fn close_subscriptions(&mut self) {
+        self.show_subscriptions = false;
+    }
+
+    fn clamp_subscription_selection(&mut self) {
+        self.subscription_selected = self
+            .subscription_selected
+            .min(self.subscriptions.len().saturating_sub(1));
+    }
+
+    /// Move subscription selection up
+    pub fn select_previous_subscription(&mut self) {
+        self.subscription_selected = self.subscription_selected.saturating_sub(1);
+    }
+
+    /// Move subscription selection down
+    pub fn select_next_subscription(&mut self) {
+        if self.subscription_selected + 1 < self.subscriptions.len() {
+            self.subscription_selected += 1;
+        }
+    }
+
+    /// Enable or disable the selected subscription
+    pub fn toggle_subscription(&mut self) {
+        self.store_nodes();
+        if let Some(subscription) = self.subscriptions.get_mut(self.subscription_selected) {
+            subscription.enabled = !subscription.enabled;
+            self.reload_nodes();
             self.save_config();
-            self.set_status("URL saved. Press R to refresh subscription.");
         }
-        self.input_mode = false;
-        self.input_buffer.clear();
     }
 
-    /// Save current state to config file
-    pub fn save_config(&self) {
-        let mut config = Config::load();
-        config.subscribe_url = self.subscribe_url.clone();
-        config.update_nodes(&self.nodes);
-        config.sort_column = self.sort_column.to_str().map(String::from);
-        config.sort_direction = if self.sort_column != SortColumn::None {
-            Some(self.sort_direction.to_str().to_string())
+    /// Move the selected subscription one place up or down in the list
+    pub fn move_subscription(&mut self, up: bool) {
+        let from = self.subscription_selected;
+        let to = if up {
+            from.checked_sub(1)
         } else {
-            None
+            Some(from + 1).filter(|to| *to < self.subscriptions.len())
+        };
+        let Some(to) = to else {
+            return;
         };
-        if let Err(e) = config.save() {
-            eprintln!("Failed to save config: {e}");
+        self.store_nodes();
+        self.subscriptions.swap(from, to);
+        self.subscription_selected = to;
+        self.reload_nodes();
+        self.save_config();
+    }
+
+    /// Ask to confirm removing the selected subscription
+    pub fn ask_remove_subscription(&mut self) {
+        if self.subscription_selected < self.subscriptions.len() {
+            self.pending_removal = Some(self.subscription_selected);
+        }
+    }
+
+    /// Keep the subscription waiting for removal
+    pub fn cancel_remove_subscription(&mut self) {
+        self.pending_removal = None;
+    }
+
+    /// Remove the subscription confirmed for removal and its nodes
+    pub fn remove_subscription(&mut self) {
+        let Some(index) = self
+            .pending_removal
+            .take()
+            .filter(|i| *i < self.subscriptions.len())
+        else {
+            return;
+        };
+        self.store_nodes();
+        let removed = self.subscriptions.remove(index);
+        self.clamp_subscription_selection();
+        self.reload_nodes();
+        self.save_config();
+        self.set_status(format!("Removed subscription {}", removed.name));
+    }
+
+    /// Open the form to add a subscription
+    pub fn add_subscription(&mut self) {
+        self.input = Some(InputForm::subscription(None, None));
+    }
+
+    /// Open the form to edit the selected subscription
+    pub fn edit_subscription(&mut self) {
+        let index = self.subscription_selected;
+        if let Some(subscription) = self.subscriptions.get(index) {
+            self.input = Some(InputForm::subscription(Some(index), Some(subscription)));
         }
     }
 
+    /// Open the dialog for adding nodes from share links
+    pub fn add_manual_nodes(&mut self) {
+        self.input = Some(InputForm::manual_nodes());
+    }
+
+    /// Remove the selected node if it belongs to the manual group
+    pub fn remove_selected_manual_node(&mut self) {
+        let Some(indexed) = self.sorted_nodes.get(self.selected) else {
+            return;
+        };
+        if indexed.source != MANUAL_SOURCE {
+            self.set_status("Only nodes in the manual group can be deleted");
+            return;
+        }
+        let name = indexed.node.display_name();
+        let index = indexed.original_index;
+        let position = self.selected;
+        self.nodes.remove(index);
+        self.node_sources.remove(index);
+        self.store_nodes();
+        self.reload_nodes();
+        self.selected = position.min(self.sorted_nodes.len().saturating_sub(1));
+        self.save_config();
+        self.set_status(format!("Deleted {name} from the manual group"));
+    }
+
+    /// Close the input form without saving
+    pub fn cancel_input(&mut self) {
+        self.input = None;
+    }
+
+    /// Validate and apply the input form
+    pub fn confirm_input(&mut self) {
+        let Some(form) = self.input.clone() else {
+            return;
+        };
+        match form.target {
+            FormTarget::Subscription(index) => {
+                let name = form.value(0);
+                let url = form.value(1);
+                if name.is_empty() || url.is_empty() {
+                    self.set_error("Subscription name and URL are required");
+                    return;
+                }
+                let fetch_via = match FetchMode::parse_list(&form.value(2)) {
+                    Ok(modes) => modes,
+                    Err(e) => {
+                        self.set_error(e.to_string());
+                        return;
+                    }
+                };
+                let request = match form.request_options() {
+                    Ok(request) => request,
+                    Err(e) => {
+                        self.set_error(e.to_string());
+                        return;
+                    }
+                };
+                let refresh_interval_mins = match form.positive_number(7) {
+                    Ok(mins) => mins,
+                    Err(e) => {
+                        self.set_error(e.to_string());
+                        return;
+                    }
+                };
+                let watch = match form.yes_no(8) {
+                    Ok(watch) => watch,
+                    Err(e) => {
+                        self.set_error(e.to_string());
+                        return;
+                    }
+                };
+                if watch && !LocalSource::parse(&url).is_some_and(|s| s.is_watchable()) {
+                    self.set_error("Only file:// sources can be watched");
+                    return;
+                }
+                let duplicate = self
+                    .subscriptions
+                    .iter()
+                    .enumerate()
+                    .any(|(i, s)| s.name == name && Some(i) != index);
+                if duplicate {
+                    self.set_error(format!("A subscription named {name} already exists"));
+                    return;
+                }
+
+                self.store_nodes();
+                match index.and_then(|i| self.subscriptions.get_mut(i)) {
+                    Some(subscription) => {
+                        subscription.name = name;
+                        subscription.url = url;
+                        subscription.fetch_via = fetch_via;
+                        subscription.request = request;
+                        subscription.refresh_interval_mins = refresh_interval_mins;
+                        subscription.watch = watch;
+                    }
+                    None => {
+                        let mut subscription = Subscription::new(name, url);
+                        subscription.fetch_via = fetch_via;
+                        subscription.request = request;
+                        subscription.refresh_interval_mins = refresh_interval_mins;
+                        subscription.watch = watch;
+                        self.subscriptions.push(subscription);
+                        self.subscription_selected = self.subscriptions.len() - 1;
+                    }
+                }
+                self.reload_nodes();
+                self.save_config();
+                self.set_status("Subscription saved. Press r to refresh it.");
+            }
+            FormTarget::ManualNodes => {
+                let links = form.value(0);
+                if links.is_empty() {
+                    self.set_error("Paste one or more share links");
+                    return;
+                }
+                let mut parsed = Vec::new();
+                let mut errors = Vec::new();
+                for link in links.split_whitespace() {
+                    match Node::from_link(link) {
+                        Ok(node) => parsed.push(node),
+                        Err(e) => errors.push(format!("{}: {e}", link_prefix(link))),
+                    }
+                }
+                if !errors.is_empty() {
+                    self.set_error(errors.join("\n"));
+                    return;
+                }
+
+                self.store_nodes();
+                let mut added = 0;
+                for node in parsed {
+                    let fingerprint = node.fingerprint();
+                    let exists = self
+                        .manual_nodes
+                        .iter()
+                        .any(|saved| Node::from(saved.clone()).fingerprint() == fingerprint);
+                    if !exists {
+                        self.manual_nodes.push(SavedNode::from(&node));
+                        added += 1;
+                    }
+                }
+                self.reload_nodes();
+                self.save_config();
+                self.set_status(format!("Added {added} node(s) to the manual group"));
+            }
+            FormTarget::ExportFile => {
+                let path = form.value(0);
+                if path.is_empty() {
+                    self.set_error("A file path is required");
+                    return;
+                }
+                let links: Vec<String> = self
+                    .export
+                    .iter()
+                    .flat_map(|e| e.links.iter().map(|(_, link)| link.clone()))
+                    .collect();
+                let path = expand_home(&path);
+                if let Err(e) = write_subscription(&path, &links) {
+                    self.set_error(e.to_string());
+                    return;
+                }
+                self.set_status(format!(
+                    "Wrote {} link(s) to {} as a base64 subscription",
+                    links.len(),
+                    path.display()
+                ));
+            }
+        }
+        self.input = None;
+    }
+
+    /// Save current state to config file
+    pub fn save_config(&mut self) {
+        self.store_nodes();
+        let subscriptions = self.subscriptions.clone();
+        let manual_nodes = self.manual_nodes.clone();
+        let (sort_column, sort_direction) = self.sort_settings();
+        self.update_config("config", |config| {
+            config.subscriptions = subscriptions;
+            config.manual_nodes = manual_nodes;
+            config.sort_column = sort_column;
+            config.sort_direction = sort_direction;
+        });
+    }
+
     /// Save only sort settings to config file
-    fn save_sort_config(&self) {
-        let mut config = Config::load();
-        config.sort_column = self.sort_column.to_str().map(String::from);
-        config.sort_direction = if self.sort_column != SortColumn::None {
-            Some(self.sort_direction.to_str().to_string())
-        } else {
-            None
+    fn save_sort_config(&mut self) {
+        let (sort_column, sort_direction) = self.sort_settings();
+        self.update_config("sort config", |config| {
+            config.sort_column = sort_column;
+            config.sort_direction = sort_direction;
+        });
+    }
+
+    /// Sort column and direction as stored in the config file
+    fn sort_settings(&self) -> (Option<String>, Option<String>) {
+        let direction = (self.sort_column != SortColumn::None)
+            .then(|| self.sort_direction.to_str().to_string());
+        (self.sort_column.to_str().map(String::from), direction)
+    }
+
+    /// Change the config file on disk, keeping the fields this app doesn't
+    /// manage. A config file that failed to load is never saved over.
+    fn update_config(&mut self, what: &str, update: impl FnOnce(&mut Config)) {
+        let mut config = match Config::load(self.config_path.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                self.set_error(format!("Not saving {what}: {e}"));
+                return;
+            }
         };
-        if let Err(e) = config.save() {
-            eprintln!("Failed to save sort config: {e}");
+        update(&mut config);
+        if let Err(e) = config.save(self.config_path.as_deref()) {
+            self.set_error(format!("Failed to save {what}: {e}"));
         }
     }
 
@@ -412,11 +1472,90 @@ impl App {
         self.active_node_index = Some(original_index);
     }
 
+    /// Original index of the listed node with the same fingerprint
+    pub fn node_index(&self, node: &Node) -> Option<usize> {
+        let fingerprint = node.fingerprint();
+        self.nodes
+            .iter()
+            .position(|n| n.fingerprint() == fingerprint)
+    }
+
+    /// Record an applied config in the history
+    pub fn record_applied(&mut self, node: &Node, config: Value) {
+        let entry = HistoryEntry {
+            node: node.clone(),
+            applied_at: unix_time(),
+            config,
+        };
+        self.history.push(entry, self.history_size);
+        self.save_history();
+    }
+
+    /// Drop the history entries newer than the one at `position` after it was re-applied
+    pub fn rewind_history(&mut self, position: usize) {
+        self.history.rewind(position);
+        self.history_selected = 0;
+        self.save_history();
+    }
+
+    fn save_history(&mut self) {
+        if let Err(e) = self.history.save(&self.xray_config_path) {
+            self.set_error(format!("Failed to save history: {e}"));
+        }
+    }
+
+    /// Open the history popup
+    pub fn open_history(&mut self) {
+        if self.history.entries.is_empty() {
+            self.set_status("No applied configs yet");
+        } else {
+            self.show_history = true;
+            self.history_selected = 0;
+        }
+    }
+
+    /// Close the history popup
+    pub fn close_history(&mut self) {
+        self.show_history = false;
+    }
+
+    /// Move the history selection up
+    pub fn history_up(&mut self) {
+        self.history_selected = self.history_selected.saturating_sub(1);
+    }
+
+    /// Move the history selection down
+    pub fn history_down(&mut self) {
+        if self.history_selected + 1 < self.history.entries.len() {
+            self.history_selected += 1;
+        }
+    }
+
     /// Get nodes for cloning (used for latency testing)
-    pub fn get_nodes_clone(&self) -> Vec<VmessNode> {
+    pub fn get_nodes_clone(&self) -> Vec<Node> {
         self.nodes.clone()
     }
 
+    /// Nodes without a result for the given test, with their original indices
+    pub fn untested_nodes(&self, test_type: TestType) -> Vec<(usize, Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.latency(test_type).is_tested())
+            .map(|(i, n)| (i, n.clone()))
+            .collect()
+    }
+
+    /// Nodes to HTTP-test; shadowsocks plugin nodes can't run in xray and stay untested
+    pub fn http_test_nodes(&self) -> Vec<(usize, Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| check_supported(n).is_ok())
+            .map(|(i, n)| (i, n.clone()))
+            .collect()
+    }
+
     /// Cancel ongoing latency test
     pub fn cancel_test(&mut self) {
         if self.testing {
@@ -496,3 +1635,345 @@ fn apply_sort_to_nodes(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscribe::{parse_subscription_content, Fetched};
+    use std::fs;
+
+    /// App whose config and xray files live in a fresh temporary directory
+    fn test_app(name: &str) -> App {
+        let dir = std::env::temp_dir().join(format!("subman-app-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let xray_config_path = dir.join("xray.json").to_string_lossy().into_owned();
+        App::with_config_path(Some(dir.join("subman.json")), 1, xray_config_path, None)
+    }
+
+    /// Delete the temporary directory of a test app
+    fn remove_test_dir(app: App) {
+        if let Some(dir) = app.config_path.as_deref().and_then(|p| p.parent()) {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    /// Saved config of a test app
+    fn saved_config(app: &App) -> Config {
+        Config::load(app.config_path.as_deref()).unwrap()
+    }
+
+    fn link_node(link: &str) -> Node {
+        Node::from_link(link).unwrap()
+    }
+
+    /// Successful fetch outcome of the subscription at `index`
+    fn fetched(index: usize, links: &[&str]) -> FetchOutcome {
+        let fetched = Fetched {
+            nodes: links.iter().map(|l| link_node(l)).collect(),
+            report: ParseReport::default(),
+            userinfo: None,
+            not_modified: false,
+            via: FetchMode::Direct,
+        };
+        (index, Ok(fetched))
+    }
+
+    /// Paste share links into the add nodes dialog and confirm it
+    fn add_manual(app: &mut App, links: &str) {
+        app.add_manual_nodes();
+        app.input.as_mut().unwrap().paste(links);
+        app.confirm_input();
+    }
+
+    fn manual_names(app: &App) -> Vec<String> {
+        app.sorted_nodes
+            .iter()
+            .filter(|n| n.source == MANUAL_SOURCE)
+            .map(|n| n.node.display_name())
+            .collect()
+    }
+
+    #[test]
+    fn test_manual_nodes_deduplicated_by_fingerprint() {
+        let mut app = test_app("manual-dedup");
+        add_manual(
+            &mut app,
+            "trojan://p@a.test.com:443#A\ntrojan://p@a.test.com:443#A-again trojan://p@b.test.com:443#B",
+        );
+        assert!(app.input.is_none());
+        assert_eq!(manual_names(&app), vec!["A", "B"]);
+        assert_eq!(app.status, "Added 2 node(s) to the manual group");
+
+        // The same server under another name is not added twice
+        add_manual(&mut app, "trojan://p@b.test.com:443#B2");
+        assert_eq!(app.status, "Added 0 node(s) to the manual group");
+        assert_eq!(saved_config(&app).manual_nodes.len(), 2);
+
+        // An invalid link keeps the dialog open and adds nothing
+        add_manual(&mut app, "trojan://p@c.test.com:443#C vmess://broken");
+        assert!(app.error_message.is_some());
+        assert!(app.input.is_some());
+        assert_eq!(manual_names(&app).len(), 2);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_manual_nodes_survive_refresh() {
+        let mut app = test_app("manual-refresh");
+        app.subscriptions
+            .push(Subscription::new("sub", "https://sub.test.com/a"));
+        add_manual(&mut app, "trojan://p@m.test.com:443#Mine");
+
+        app.apply_refresh(vec![fetched(
+            0,
+            &[
+                "trojan://p@s1.test.com:443#S1",
+                "trojan://p@s2.test.com:443#S2",
+            ],
+        )]);
+        assert_eq!(app.nodes.len(), 3);
+        assert_eq!(manual_names(&app), vec!["Mine"]);
+
+        // A refresh dropping every subscription node leaves the manual group alone
+        app.apply_refresh(vec![fetched(0, &[])]);
+        assert_eq!(app.nodes.len(), 1);
+        assert_eq!(manual_names(&app), vec!["Mine"]);
+        let config = saved_config(&app);
+        assert!(config.subscriptions[0].nodes.is_empty());
+        assert_eq!(config.manual_nodes.len(), 1);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_only_manual_nodes_can_be_deleted() {
+        let mut app = test_app("manual-delete");
+        app.subscriptions
+            .push(Subscription::new("sub", "https://sub.test.com/a"));
+        app.apply_refresh(vec![fetched(0, &["trojan://p@s1.test.com:443#S1"])]);
+        add_manual(
+            &mut app,
+            "trojan://p@m1.test.com:443#M1 trojan://p@m2.test.com:443#M2",
+        );
+
+        let position = |app: &App, source: usize| {
+            app.sorted_nodes
+                .iter()
+                .position(|n| n.source == source)
+                .unwrap()
+        };
+        app.selected = position(&app, 0);
+        app.remove_selected_manual_node();
+        assert_eq!(app.status, "Only nodes in the manual group can be deleted");
+        assert_eq!(app.nodes.len(), 3);
+
+        app.selected = position(&app, MANUAL_SOURCE);
+        app.remove_selected_manual_node();
+        assert_eq!(app.nodes.len(), 2);
+        assert_eq!(manual_names(&app), vec!["M2"]);
+        assert_eq!(saved_config(&app).manual_nodes.len(), 1);
+        assert_eq!(saved_config(&app).subscriptions[0].nodes.len(), 1);
+        remove_test_dir(app);
+    }
+
+    /// Fill fields of the open subscription form (by index) and confirm it
+    fn submit_subscription(app: &mut App, values: &[(usize, &str)]) {
+        let form = app.input.as_mut().unwrap();
+        for (index, value) in values {
+            form.fields[*index].value = value.to_string();
+        }
+        app.confirm_input();
+    }
+
+    fn subscription_names(app: &App) -> Vec<&str> {
+        app.subscriptions.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_subscription_manager() {
+        let mut app = test_app("subscriptions");
+
+        // Add
+        for (name, url) in [
+            ("a", "https://a.test.com/sub"),
+            ("b", "https://b.test.com/sub"),
+        ] {
+            app.add_subscription();
+            submit_subscription(&mut app, &[(0, name), (1, url)]);
+            assert!(app.input.is_none(), "{:?}", app.error_message);
+        }
+        assert_eq!(subscription_names(&app), vec!["a", "b"]);
+        assert_eq!(app.subscription_selected, 1);
+        app.add_subscription();
+        submit_subscription(&mut app, &[(0, "a"), (1, "https://c.test.com/sub")]);
+        assert!(app.error_message.take().unwrap().contains("already exists"));
+        app.cancel_input();
+
+        // Edit keeps the subscription's nodes
+        app.apply_refresh(vec![fetched(1, &["trojan://p@b1.test.com:443#B1"])]);
+        app.edit_subscription();
+        assert_eq!(app.input.as_ref().unwrap().value(0), "b");
+        submit_subscription(&mut app, &[(0, "b2"), (4, "Cookie: x=1; y=2")]);
+        assert_eq!(subscription_names(&app), vec!["a", "b2"]);
+        assert_eq!(app.subscriptions[1].request.headers["Cookie"], "x=1; y=2");
+        assert_eq!(app.subscriptions[1].nodes.len(), 1);
+
+        // Move
+        app.move_subscription(true);
+        assert_eq!(subscription_names(&app), vec!["b2", "a"]);
+        assert_eq!(app.subscription_selected, 0);
+        app.move_subscription(true);
+        assert_eq!(subscription_names(&app), vec!["b2", "a"]);
+        assert_eq!(app.node_sources, vec![0]);
+
+        // Toggle hides the nodes of a disabled subscription
+        app.toggle_subscription();
+        assert!(!app.subscriptions[0].enabled);
+        assert!(app.nodes.is_empty());
+        app.toggle_subscription();
+        assert_eq!(app.nodes.len(), 1);
+
+        // Remove only after confirming
+        app.ask_remove_subscription();
+        assert_eq!(app.pending_removal, Some(0));
+        app.cancel_remove_subscription();
+        app.remove_subscription();
+        assert_eq!(subscription_names(&app), vec!["b2", "a"]);
+
+        app.ask_remove_subscription();
+        app.remove_subscription();
+        assert_eq!(subscription_names(&app), vec!["a"]);
+        assert!(app.pending_removal.is_none());
+        assert!(app.nodes.is_empty());
+
+        let config = saved_config(&app);
+        let names: Vec<&str> = config
+            .subscriptions
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a"]);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_parse_report_popup() {
+        let mut app = test_app("report");
+        app.open_report();
+        assert!(!app.show_report);
+        assert_eq!(app.status, "No parse report yet. Press R to refresh.");
+
+        for (name, url) in [
+            ("a", "https://a.test.com/sub"),
+            ("b", "https://b.test.com/sub"),
+        ] {
+            app.subscriptions.push(Subscription::new(name, url));
+        }
+        let body = "trojan://p@a1.test.com:443#A1\nhysteria2://p@h.test.com:443#H\ntrojan://@bad\n";
+        let (nodes, report) = parse_subscription_content(body, &app.info_rules).unwrap();
+        let outcome = Fetched {
+            nodes,
+            report,
+            userinfo: None,
+            not_modified: false,
+            via: FetchMode::Direct,
+        };
+        app.apply_refresh(vec![
+            (0, Ok(outcome)),
+            fetched(1, &["trojan://p@b1.test.com:443#B1"]),
+        ]);
+        assert!(app.status.ends_with(". Press P for details"));
+
+        // One report per fetched subscription, with every skipped or invalid line
+        let names: Vec<&str> = app.parse_reports.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        let issues = &app.parse_reports[0].1.issues;
+        let lines: Vec<usize> = issues.iter().map(|i| i.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+        assert!(app.parse_reports[1].1.issues.is_empty());
+
+        // Heading + 2 issues, heading + "no problems": 5 lines, the last is 4
+        app.open_report();
+        assert!(app.show_report);
+        app.scroll_report_down(2);
+        assert_eq!(app.report_scroll, 2);
+        app.scroll_report_down(10);
+        assert_eq!(app.report_scroll, 4);
+        app.scroll_report_up(1);
+        assert_eq!(app.report_scroll, 3);
+        app.scroll_report_up(10);
+        assert_eq!(app.report_scroll, 0);
+
+        app.scroll_report_down(10);
+        app.close_report();
+        app.open_report();
+        assert_eq!(app.report_scroll, 0);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_http_test_skips_plugin_nodes() {
+        let mut app = test_app("http-test");
+        add_manual(
+            &mut app,
+            "trojan://p@a.test.com:443#A ss://YWVzLTEyOC1nY206cGFzcw@s.test.com:8388?plugin=obfs-local%3Bobfs%3Dhttp#P",
+        );
+        assert_eq!(app.nodes.len(), 2);
+        let names: Vec<String> = app
+            .http_test_nodes()
+            .iter()
+            .map(|(_, n)| n.display_name())
+            .collect();
+        assert_eq!(names, vec!["A"]);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_refresh_attempts_survive_rename() {
+        let mut app = test_app("refresh-attempts");
+        app.add_subscription();
+        submit_subscription(
+            &mut app,
+            &[(0, "a"), (1, "https://a.test.com/sub"), (7, "10")],
+        );
+        assert_eq!(app.due_refresh_targets(1000).len(), 1);
+        assert_eq!(app.next_auto_refresh(1000), Some(600));
+
+        app.edit_subscription();
+        submit_subscription(&mut app, &[(0, "renamed")]);
+        assert!(app.due_refresh_targets(1001).is_empty());
+        assert_eq!(app.next_auto_refresh(1000), Some(600));
+
+        // A new URL is a new source, and the old URL's attempt is dropped
+        app.edit_subscription();
+        submit_subscription(&mut app, &[(1, "https://b.test.com/sub")]);
+        assert!(app.refresh_attempts.is_empty());
+        assert_eq!(app.due_refresh_targets(1001).len(), 1);
+        remove_test_dir(app);
+    }
+
+    #[test]
+    fn test_source_stamps_survive_rename() {
+        let mut app = test_app("source-stamps");
+        let dir = app.config_path.as_deref().unwrap().parent().unwrap();
+        let source = dir.join("nodes.txt");
+        fs::write(&source, "trojan://p@a.test.com:443#A\n").unwrap();
+        let url = format!("file://{}", source.display());
+
+        app.add_subscription();
+        submit_subscription(&mut app, &[(0, "local"), (1, &url), (8, "y")]);
+        assert!(app.input.is_none(), "{:?}", app.error_message);
+        assert_eq!(app.changed_sources(1).len(), 1);
+        assert!(app.changed_sources(2).is_empty());
+
+        // Renaming is not a change of the watched file
+        app.edit_subscription();
+        submit_subscription(&mut app, &[(0, "renamed")]);
+        assert!(app.changed_sources(3).is_empty());
+
+        app.ask_remove_subscription();
+        app.remove_subscription();
+        assert!(app.source_stamps.is_empty());
+        remove_test_dir(app);
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Last good subscription body with the validators needed for a conditional request
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedBody {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// Key of the request options the body was fetched with; the validators
+    /// are only sent again with the same options
+    #[serde(default)]
+    pub request_key: u64,
+    pub body: String,
+}
+
+impl CachedBody {
+    /// Get the cache directory (~/.cache/subman)
+    pub fn cache_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|p| p.join("subman"))
+    }
+
+    /// Cache file of a subscription URL
+    fn path(url: &str) -> Option<PathBuf> {
+        Self::cache_dir().map(|dir| dir.join(format!("{:016x}.json", fnv1a(url))))
+    }
+
+    /// Load the cached body of a URL, if any
+    pub fn load(url: &str) -> Option<CachedBody> {
+        let content = fs::read_to_string(Self::path(url)?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Save as the cached body of a URL
+    pub fn save(&self, url: &str) -> Result<()> {
+        let path = Self::path(url).ok_or_else(|| anyhow!("Could not determine cache directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across Rust versions unlike `DefaultHasher`
+pub fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_file_name_is_stable() {
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(
+            fnv1a("https://a.test.com/sub"),
+            fnv1a("https://b.test.com/sub")
+        );
+    }
+}
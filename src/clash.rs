@@ -0,0 +1,661 @@
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+
+use crate::export::with_unique_names;
+use crate::node::{Node, ProtocolSettings, RealitySettings, Security, TlsSettings, Transport};
+use crate::shadowsocks::check_method;
+
+/// Check whether content looks like a Clash/mihomo profile with a proxies list
+pub fn is_clash_yaml(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.trim_end() == "proxies:" || line.starts_with("proxies: ["))
+}
+
+/// Parse a Clash/mihomo YAML profile, returning one result per `proxies` entry
+pub fn parse_clash_yaml(content: &str) -> Result<Vec<Result<Node>>> {
+    let profile: Value =
+        serde_yaml::from_str(content).map_err(|e| anyhow!("YAML parse error: {e}"))?;
+
+    let proxies = profile
+        .get("proxies")
+        .and_then(Value::as_sequence)
+        .ok_or_else(|| anyhow!("Clash profile has no proxies list"))?;
+
+    Ok(proxies.iter().map(proxy_to_node).collect())
+}
+
+/// Read a scalar field as a string (numbers and booleans included)
+fn string_field(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::Bool(b)) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn bool_field(value: &Value, key: &str) -> bool {
+    value.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Read a field that may be a single string or a list of strings, joined by commas
+fn list_field(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(","),
+        Some(Value::String(s)) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Convert one Clash proxy entry into a Node
+fn proxy_to_node(proxy: &Value) -> Result<Node> {
+    let kind = string_field(proxy, "type");
+    let name = string_field(proxy, "name");
+    let address = string_field(proxy, "server");
+    let port = string_field(proxy, "port")
+        .parse()
+        .map_err(|_| anyhow!("Invalid port for proxy {name}"))?;
+    if address.is_empty() {
+        return Err(anyhow!("Missing server for proxy {name}"));
+    }
+
+    let settings = match kind.as_str() {
+        "vmess" => ProtocolSettings::Vmess {
+            id: string_field(proxy, "uuid"),
+            alter_id: string_field(proxy, "alterId").parse().unwrap_or(0),
+        },
+        "vless" => ProtocolSettings::Vless {
+            id: string_field(proxy, "uuid"),
+            flow: string_field(proxy, "flow"),
+            encryption: String::new(),
+        },
+        "trojan" => ProtocolSettings::Trojan {
+            password: string_field(proxy, "password"),
+        },
+        "ss" => {
+            let (plugin, plugin_opts) = plugin_fields(proxy);
+            ProtocolSettings::Shadowsocks {
+                method: check_method(&string_field(proxy, "cipher"))?,
+                password: string_field(proxy, "password"),
+                plugin,
+                plugin_opts,
+            }
+        }
+        other => return Err(anyhow!("Unsupported proxy type {other:?} for {name}")),
+    };
+
+    let mut node = Node::new(name, address, port, settings);
+    if kind != "ss" {
+        node.transport = transport(proxy);
+        node.security = security(&kind, proxy);
+    }
+    Ok(node)
+}
+
+/// Map Clash network options to a Transport
+fn transport(proxy: &Value) -> Transport {
+    let network = string_field(proxy, "network");
+    let empty = Value::Null;
+    let mut transport = Transport::default();
+
+    match network.as_str() {
+        // mihomo writes HTTPUpgrade as ws with `v2ray-http-upgrade: true`
+        "ws" | "httpupgrade" => {
+            let opts = proxy.get("ws-opts").unwrap_or(&empty);
+            transport.network = if bool_field(opts, "v2ray-http-upgrade") {
+                "httpupgrade".to_string()
+            } else {
+                network.clone()
+            };
+            transport.path = string_field(opts, "path");
+            transport.host = opts
+                .get("headers")
+                .map(|h| string_field(h, "Host"))
+                .unwrap_or_default();
+        }
+        "grpc" => {
+            let opts = proxy.get("grpc-opts").unwrap_or(&empty);
+            transport.network = network;
+            transport.service_name = string_field(opts, "grpc-service-name");
+        }
+        "h2" => {
+            let opts = proxy.get("h2-opts").unwrap_or(&empty);
+            transport.network = network;
+            transport.host = list_field(opts, "host");
+            transport.path = string_field(opts, "path");
+        }
+        // Clash "http" is HTTP/1.1 header obfuscation over plain TCP
+        "http" => {
+            let opts = proxy.get("http-opts").unwrap_or(&empty);
+            transport.network = "tcp".to_string();
+            transport.header_type = "http".to_string();
+            transport.path = list_field(opts, "path")
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            transport.host = opts
+                .get("headers")
+                .map(|h| list_field(h, "Host"))
+                .unwrap_or_default()
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+        }
+        _ => transport.network = "tcp".to_string(),
+    }
+
+    transport
+}
+
+/// Map Clash TLS/REALITY options to a Security layer
+fn security(kind: &str, proxy: &Value) -> Security {
+    let sni = match kind {
+        "trojan" => string_field(proxy, "sni"),
+        _ => string_field(proxy, "servername"),
+    };
+    let fingerprint = string_field(proxy, "client-fingerprint");
+
+    if let Some(reality) = proxy.get("reality-opts") {
+        return Security::Reality(RealitySettings {
+            sni,
+            fingerprint,
+            public_key: string_field(reality, "public-key"),
+            short_id: string_field(reality, "short-id"),
+            spider_x: String::new(),
+        });
+    }
+
+    // Trojan is always TLS, vmess/vless only when tls: true
+    if kind == "trojan" || bool_field(proxy, "tls") {
+        Security::Tls(TlsSettings {
+            sni,
+            alpn: list_field(proxy, "alpn"),
+            fingerprint,
+            allow_insecure: bool_field(proxy, "skip-cert-verify"),
+        })
+    } else {
+        Security::None
+    }
+}
+
+/// Convert Clash plugin/plugin-opts to SIP003 plugin name and options, e.g.
+/// `obfs` with `{mode: http, host: a.com}` to `obfs-local` with
+/// `obfs=http;obfs-host=a.com`
+fn plugin_fields(proxy: &Value) -> (String, String) {
+    let plugin = string_field(proxy, "plugin");
+    let opts: Vec<(String, String)> = match proxy.get("plugin-opts").and_then(Value::as_mapping) {
+        Some(map) => map
+            .iter()
+            .filter_map(|(k, v)| {
+                let key = k.as_str()?;
+                let value = match v {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => return None,
+                };
+                Some((key.to_string(), value))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let get = |key: &str| {
+        opts.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty())
+    };
+
+    let sip003: Vec<String> = match plugin.as_str() {
+        "obfs" => [("obfs", get("mode")), ("obfs-host", get("host"))]
+            .into_iter()
+            .filter_map(|(key, value)| Some(format!("{key}={}", value?)))
+            .collect(),
+        "v2ray-plugin" => {
+            let mut sip003 = Vec::new();
+            if let Some(mode) = get("mode").filter(|m| *m != "websocket") {
+                sip003.push(format!("mode={mode}"));
+            }
+            if get("tls") == Some("true") {
+                sip003.push("tls".to_string());
+            }
+            for key in ["host", "path"] {
+                if let Some(value) = get(key) {
+                    sip003.push(format!("{key}={value}"));
+                }
+            }
+            sip003
+        }
+        _ => opts.iter().map(|(k, v)| format!("{k}={v}")).collect(),
+    };
+    let plugin = match plugin.as_str() {
+        "obfs" => "obfs-local".to_string(),
+        _ => plugin,
+    };
+    (plugin, sip003.join(";"))
+}
+
+/// Build a Clash profile with the nodes as proxies, a selector group and a catch-all rule
+pub fn clash_profile(nodes: &[Node]) -> Result<String> {
+    // Proxy and group names share one namespace with the built-in policies
+    let nodes = &with_unique_names(nodes, &["PROXY", "DIRECT", "REJECT"]);
+    let names: Vec<Value> = nodes.iter().map(|n| n.display_name().into()).collect();
+    let mut group = Mapping::new();
+    group.insert("name".into(), "PROXY".into());
+    group.insert("type".into(), "select".into());
+    group.insert("proxies".into(), Value::Sequence(names));
+
+    let mut profile = Mapping::new();
+    profile.insert(
+        "proxies".into(),
+        Value::Sequence(nodes.iter().map(node_to_proxy).collect()),
+    );
+    profile.insert(
+        "proxy-groups".into(),
+        Value::Sequence(vec![Value::Mapping(group)]),
+    );
+    profile.insert("rules".into(), Value::Sequence(vec!["MATCH,PROXY".into()]));
+    serde_yaml::to_string(&profile).map_err(|e| anyhow!("YAML encode error: {e}"))
+}
+
+/// Convert a Node into a Clash proxy entry
+pub fn node_to_proxy(node: &Node) -> Value {
+    let mut proxy = Mapping::new();
+    let mut set = |key: &str, value: Value| {
+        proxy.insert(key.into(), value);
+    };
+    set("name", node.display_name().into());
+    set("server", node.address.clone().into());
+    set("port", node.port.into());
+
+    match &node.settings {
+        ProtocolSettings::Vmess { id, alter_id } => {
+            set("type", "vmess".into());
+            set("uuid", id.clone().into());
+            set("alterId", (*alter_id).into());
+            set("cipher", "auto".into());
+        }
+        ProtocolSettings::Vless { id, flow, .. } => {
+            set("type", "vless".into());
+            set("uuid", id.clone().into());
+            if !flow.is_empty() {
+                set("flow", flow.clone().into());
+            }
+        }
+        ProtocolSettings::Trojan { password } => {
+            set("type", "trojan".into());
+            set("password", password.clone().into());
+        }
+        ProtocolSettings::Shadowsocks {
+            method,
+            password,
+            plugin,
+            plugin_opts,
+        } => {
+            set("type", "ss".into());
+            set("cipher", method.clone().into());
+            set("password", password.clone().into());
+            if !plugin.is_empty() {
+                let (plugin, opts) = clash_plugin(plugin, plugin_opts);
+                set("plugin", plugin.into());
+                set("plugin-opts", opts);
+            }
+            return Value::Mapping(proxy);
+        }
+    }
+
+    let is_trojan = matches!(node.settings, ProtocolSettings::Trojan { .. });
+    for (key, value) in transport_fields(&node.transport) {
+        set(key, value);
+    }
+    for (key, value) in security_fields(is_trojan, &node.security) {
+        set(key, value);
+    }
+    Value::Mapping(proxy)
+}
+
+/// Build a mapping from string keys and values
+fn mapping<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Mapping(
+        entries
+            .into_iter()
+            .map(|(k, v)| (Value::from(k), v))
+            .collect(),
+    )
+}
+
+/// Clash network options of a Transport
+fn transport_fields(transport: &Transport) -> Vec<(&'static str, Value)> {
+    let t = transport;
+    match t.network.as_str() {
+        "ws" => vec![
+            ("network", "ws".into()),
+            (
+                "ws-opts",
+                mapping([
+                    ("path", t.path.clone().into()),
+                    ("headers", mapping([("Host", t.host.clone().into())])),
+                ]),
+            ),
+        ],
+        "httpupgrade" => vec![
+            ("network", "ws".into()),
+            (
+                "ws-opts",
+                mapping([
+                    ("path", t.path.clone().into()),
+                    ("headers", mapping([("Host", t.host.clone().into())])),
+                    ("v2ray-http-upgrade", true.into()),
+                ]),
+            ),
+        ],
+        "grpc" => vec![
+            ("network", "grpc".into()),
+            (
+                "grpc-opts",
+                mapping([("grpc-service-name", t.service_name.clone().into())]),
+            ),
+        ],
+        "h2" => vec![
+            ("network", "h2".into()),
+            (
+                "h2-opts",
+                mapping([
+                    ("host", split_list(&t.host)),
+                    ("path", t.path.clone().into()),
+                ]),
+            ),
+        ],
+        _ if t.header_type == "http" => vec![
+            ("network", "http".into()),
+            (
+                "http-opts",
+                mapping([
+                    ("path", split_list(&t.path)),
+                    ("headers", mapping([("Host", split_list(&t.host))])),
+                ]),
+            ),
+        ],
+        _ => vec![("network", "tcp".into())],
+    }
+}
+
+/// Clash TLS/REALITY options of a Security layer
+fn security_fields(is_trojan: bool, security: &Security) -> Vec<(&'static str, Value)> {
+    let sni_key = if is_trojan { "sni" } else { "servername" };
+    let mut fields = Vec::new();
+    match security {
+        Security::None => {}
+        Security::Tls(tls) => {
+            fields.push(("tls", true.into()));
+            if !tls.sni.is_empty() {
+                fields.push((sni_key, tls.sni.clone().into()));
+            }
+            if !tls.alpn.is_empty() {
+                fields.push(("alpn", split_list(&tls.alpn)));
+            }
+            if !tls.fingerprint.is_empty() {
+                fields.push(("client-fingerprint", tls.fingerprint.clone().into()));
+            }
+            if tls.allow_insecure {
+                fields.push(("skip-cert-verify", true.into()));
+            }
+        }
+        Security::Reality(reality) => {
+            fields.push(("tls", true.into()));
+            fields.push((sni_key, reality.sni.clone().into()));
+            if !reality.fingerprint.is_empty() {
+                fields.push(("client-fingerprint", reality.fingerprint.clone().into()));
+            }
+            fields.push((
+                "reality-opts",
+                mapping([
+                    ("public-key", reality.public_key.clone().into()),
+                    ("short-id", reality.short_id.clone().into()),
+                ]),
+            ));
+        }
+    }
+    fields
+}
+
+/// Split a comma-separated value into a list
+fn split_list(value: &str) -> Value {
+    Value::Sequence(
+        value
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string().into())
+            .collect(),
+    )
+}
+
+/// Convert a SIP003 plugin and its options ("key=value;flag") to a Clash
+/// plugin name and plugin-opts mapping
+fn clash_plugin(plugin: &str, opts: &str) -> (String, Value) {
+    let pairs: Vec<(&str, Option<&str>)> = opts
+        .split(';')
+        .filter(|opt| !opt.is_empty())
+        .map(|opt| match opt.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (opt, None),
+        })
+        .collect();
+    let get = |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+    let value = |key: &str| get(key).flatten();
+
+    let mut map = Mapping::new();
+    let name = match plugin {
+        "obfs-local" | "simple-obfs" => {
+            if let Some(mode) = value("obfs") {
+                map.insert("mode".into(), mode.into());
+            }
+            if let Some(host) = value("obfs-host") {
+                map.insert("host".into(), host.into());
+            }
+            "obfs"
+        }
+        "v2ray-plugin" => {
+            map.insert("mode".into(), value("mode").unwrap_or("websocket").into());
+            if get("tls").is_some() {
+                map.insert("tls".into(), true.into());
+            }
+            for key in ["host", "path"] {
+                if let Some(v) = value(key) {
+                    map.insert(key.into(), v.into());
+                }
+            }
+            "v2ray-plugin"
+        }
+        other => {
+            for (key, v) in &pairs {
+                map.insert((*key).into(), v.map_or(true.into(), Value::from));
+            }
+            other
+        }
+    };
+    (name.to_string(), Value::Mapping(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Protocol;
+
+    #[test]
+    fn test_parse_clash_yaml() {
+        let content = r#"
+port: 7890
+proxies:
+  - name: "vmess-ws"
+    type: vmess
+    server: v.test.com
+    port: 443
+    uuid: uuid1
+    alterId: 0
+    cipher: auto
+    tls: true
+    servername: cdn.test.com
+    network: ws
+    ws-opts:
+      path: /ws
+      headers:
+        Host: cdn.test.com
+  - name: vless-reality
+    type: vless
+    server: 1.2.3.4
+    port: 443
+    uuid: uuid2
+    flow: xtls-rprx-vision
+    network: tcp
+    tls: true
+    servername: www.microsoft.com
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: pubkey
+      short-id: abcd
+  - {name: trojan-grpc, type: trojan, server: t.test.com, port: 443, password: secret, sni: t.test.com, network: grpc, grpc-opts: {grpc-service-name: svc}}
+  - {name: ss, type: ss, server: s.test.com, port: "8388", cipher: aes-128-gcm, password: pass, plugin: obfs, plugin-opts: {mode: http, host: bing.com}}
+  - {name: hy2, type: hysteria2, server: h.test.com, port: 443, password: pass}
+proxy-groups: []
+"#;
+
+        assert!(is_clash_yaml(content));
+        let results = parse_clash_yaml(content).unwrap();
+        assert_eq!(results.len(), 5);
+
+        let vmess = results[0].as_ref().unwrap();
+        assert_eq!(vmess.protocol(), Protocol::Vmess);
+        assert_eq!(vmess.name, "vmess-ws");
+        assert_eq!(vmess.transport.network, "ws");
+        assert_eq!(vmess.transport.host, "cdn.test.com");
+        assert!(matches!(vmess.security, Security::Tls(_)));
+
+        let vless = results[1].as_ref().unwrap();
+        let Security::Reality(reality) = &vless.security else {
+            panic!("expected reality security");
+        };
+        assert_eq!(reality.public_key, "pubkey");
+        assert_eq!(reality.short_id, "abcd");
+
+        let trojan = results[2].as_ref().unwrap();
+        assert_eq!(trojan.transport.service_name, "svc");
+
+        let ss = results[3].as_ref().unwrap();
+        assert_eq!(ss.port, 8388);
+        assert!(matches!(
+            &ss.settings,
+            ProtocolSettings::Shadowsocks { plugin, plugin_opts, .. }
+                if plugin == "obfs-local" && plugin_opts == "obfs=http;obfs-host=bing.com"
+        ));
+
+        let unsupported = results[4].as_ref().unwrap_err().to_string();
+        assert!(unsupported.contains("hysteria2"));
+    }
+
+    #[test]
+    fn test_clash_profile_roundtrip() {
+        let content = r#"
+proxies:
+  - {name: vmess-h2, type: vmess, server: v.test.com, port: 443, uuid: u, alterId: 0, tls: true, servername: v.test.com, alpn: [h2], network: h2, h2-opts: {host: [v.test.com], path: /h2}}
+  - {name: vless-reality, type: vless, server: 1.2.3.4, port: 443, uuid: u, flow: xtls-rprx-vision, tls: true, servername: a.com, client-fingerprint: chrome, reality-opts: {public-key: k, short-id: ab}}
+  - {name: trojan-grpc, type: trojan, server: t.test.com, port: 443, password: p, sni: t.test.com, network: grpc, grpc-opts: {grpc-service-name: svc}}
+  - {name: vmess-http, type: vmess, server: h.test.com, port: 80, uuid: u, alterId: 0, network: http, http-opts: {path: [/], headers: {Host: [h.test.com]}}}
+  - {name: ss, type: ss, server: s.test.com, port: 8388, cipher: aes-128-gcm, password: p, plugin: obfs, plugin-opts: {mode: http, host: bing.com}}
+  - {name: vless-upgrade, type: vless, server: u.test.com, port: 80, uuid: u, network: ws, ws-opts: {path: /up, headers: {Host: cdn.test.com}, v2ray-http-upgrade: true}}
+"#;
+        let nodes: Vec<Node> = parse_clash_yaml(content)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        let profile = clash_profile(&nodes).unwrap();
+        let restored: Vec<Node> = parse_clash_yaml(&profile)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(restored.len(), nodes.len());
+        for (restored, node) in restored.iter().zip(&nodes) {
+            assert!(restored.same_config(node), "{}", node.name);
+        }
+        assert!(profile.contains("MATCH,PROXY"));
+
+        // HTTPUpgrade is ws with a flag in mihomo, never a network of its own
+        assert_eq!(nodes[5].transport.network, "httpupgrade");
+        assert_eq!(nodes[5].transport.host, "cdn.test.com");
+        assert!(!profile.contains("network: httpupgrade"));
+        assert!(profile.contains("v2ray-http-upgrade: true"));
+
+        // Repeated names are made unique in proxies and the group
+        let twice = clash_profile(&[nodes[2].clone(), nodes[2].clone()]).unwrap();
+        let names: Vec<String> = parse_clash_yaml(&twice)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.unwrap().name)
+            .collect();
+        assert_eq!(names, vec!["trojan-grpc", "trojan-grpc (2)"]);
+        assert!(twice.contains("- trojan-grpc (2)"));
+    }
+
+    #[test]
+    fn test_plugin_conversion() {
+        let content = r#"
+proxies:
+  - {name: obfs, type: ss, server: s.test.com, port: 8388, cipher: aes-128-gcm, password: p, plugin: obfs, plugin-opts: {mode: tls, host: bing.com}}
+  - {name: v2ray, type: ss, server: s.test.com, port: 443, cipher: aes-128-gcm, password: p, plugin: v2ray-plugin, plugin-opts: {mode: websocket, tls: true, host: cdn.test.com, path: /ws}}
+"#;
+        let nodes: Vec<Node> = parse_clash_yaml(content)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        let sip003: Vec<(&str, &str)> = nodes
+            .iter()
+            .map(|node| match &node.settings {
+                ProtocolSettings::Shadowsocks {
+                    plugin,
+                    plugin_opts,
+                    ..
+                } => (plugin.as_str(), plugin_opts.as_str()),
+                other => panic!("{other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            sip003,
+            vec![
+                ("obfs-local", "obfs=tls;obfs-host=bing.com"),
+                ("v2ray-plugin", "tls;host=cdn.test.com;path=/ws"),
+            ]
+        );
+
+        // sing-box takes the SIP003 form as is
+        let config = crate::singbox::singbox_config(&nodes);
+        assert_eq!(config["outbounds"][1]["plugin"], "obfs-local");
+        assert_eq!(
+            config["outbounds"][2]["plugin_opts"],
+            "tls;host=cdn.test.com;path=/ws"
+        );
+
+        // Clash export converts back to Clash plugin-opts
+        let profile = clash_profile(&nodes).unwrap();
+        let value: Value = serde_yaml::from_str(&profile).unwrap();
+        let proxies = value["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies[0]["plugin"].as_str(), Some("obfs"));
+        assert_eq!(proxies[0]["plugin-opts"]["mode"].as_str(), Some("tls"));
+        assert_eq!(
+            proxies[1]["plugin-opts"]["mode"].as_str(),
+            Some("websocket")
+        );
+        assert_eq!(proxies[1]["plugin-opts"]["tls"].as_bool(), Some(true));
+        assert_eq!(proxies[1]["plugin-opts"]["path"].as_str(), Some("/ws"));
+    }
+}
@@ -1,28 +1,22 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::vmess::{LatencyStatus, VmessNode};
+use crate::node::{LatencyStatus, Node};
+use crate::notice::InfoRule;
+use crate::serve::ServeOptions;
+use crate::service::ServiceBackend;
+use crate::subscribe::{FetchMode, RequestOptions};
+use crate::userinfo::UserInfo;
+use crate::vmess::VmessNode;
 
 /// Saved node data including latency measurements
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedNode {
-    pub v: String,
-    pub ps: String,
-    pub add: String,
-    pub port: serde_json::Value,
-    pub id: String,
-    pub aid: serde_json::Value,
-    pub net: String,
-    #[serde(rename = "type")]
-    pub type_field: String,
-    pub host: String,
-    pub path: String,
-    pub tls: String,
-    pub sni: String,
-    pub alpn: String,
-    pub fp: String,
+    #[serde(flatten)]
+    pub node: Node,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub http_latency: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -33,8 +27,8 @@ pub struct SavedNode {
     pub tcp_timed_out: bool,
 }
 
-impl From<&VmessNode> for SavedNode {
-    fn from(node: &VmessNode) -> Self {
+impl From<&Node> for SavedNode {
+    fn from(node: &Node) -> Self {
         let (http_latency, http_timed_out) = match node.http_latency {
             LatencyStatus::Success(ms) => (Some(ms), false),
             LatencyStatus::TimedOut => (None, true),
@@ -46,20 +40,7 @@ impl From<&VmessNode> for SavedNode {
             LatencyStatus::NotTested => (None, false),
         };
         SavedNode {
-            v: node.v.clone(),
-            ps: node.ps.clone(),
-            add: node.add.clone(),
-            port: node.port.clone(),
-            id: node.id.clone(),
-            aid: node.aid.clone(),
-            net: node.net.clone(),
-            type_field: node.type_field.clone(),
-            host: node.host.clone(),
-            path: node.path.clone(),
-            tls: node.tls.clone(),
-            sni: node.sni.clone(),
-            alpn: node.alpn.clone(),
-            fp: node.fp.clone(),
+            node: node.clone(),
             http_latency,
             tcp_latency,
             http_timed_out,
@@ -68,7 +49,7 @@ impl From<&VmessNode> for SavedNode {
     }
 }
 
-impl From<SavedNode> for VmessNode {
+impl From<SavedNode> for Node {
     fn from(saved: SavedNode) -> Self {
         let http_latency = if let Some(ms) = saved.http_latency {
             LatencyStatus::Success(ms)
@@ -84,38 +65,159 @@ impl From<SavedNode> for VmessNode {
         } else {
             LatencyStatus::NotTested
         };
-        VmessNode {
-            v: saved.v,
-            ps: saved.ps,
-            add: saved.add,
-            port: saved.port,
-            id: saved.id,
-            aid: saved.aid,
-            net: saved.net,
-            type_field: saved.type_field,
-            host: saved.host,
-            path: saved.path,
-            tls: saved.tls,
-            sni: saved.sni,
-            alpn: saved.alpn,
-            fp: saved.fp,
+        Node {
             http_latency,
             tcp_latency,
+            ..saved.node
         }
     }
 }
 
+/// Named xray setup, selectable in the TUI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// xray config template filled with the selected node's outbound
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// A named subscription source with its own cached nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub nodes: Vec<SavedNode>,
+    /// Fetch paths to try in order, direct when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fetch_via: Vec<FetchMode>,
+    /// Refresh automatically every this many minutes while subman is open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval_mins: Option<u64>,
+    /// Reload a file or directory source when it changes
+    #[serde(default)]
+    pub watch: bool,
+    /// User-Agent, extra headers, basic auth and timeout
+    #[serde(flatten)]
+    pub request: RequestOptions,
+    /// Path that succeeded on the last fetch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_via: Option<FetchMode>,
+    /// Quota and expiry reported by the provider on the last fetch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userinfo: Option<UserInfo>,
+    /// Unix timestamp of the last successful fetch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<u64>,
+    /// Unix timestamp of the first failed fetch since the last good one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_since: Option<u64>,
+    /// Text of informational pseudo-nodes from the last fetch
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Subscription {
+    /// Create an enabled subscription with no cached nodes
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Subscription {
+            name: name.into(),
+            url: url.into(),
+            enabled: true,
+            nodes: Vec::new(),
+            fetch_via: Vec::new(),
+            refresh_interval_mins: None,
+            watch: false,
+            request: RequestOptions::default(),
+            fetched_via: None,
+            userinfo: None,
+            fetched_at: None,
+            stale_since: None,
+            notices: Vec::new(),
+        }
+    }
+
+    /// Convert cached nodes to Nodes
+    pub fn to_nodes(&self) -> Vec<Node> {
+        self.nodes.iter().cloned().map(Node::from).collect()
+    }
+
+    /// Unix timestamp of the next automatic refresh, counted from the last
+    /// successful fetch or the last attempt, whichever is later
+    pub fn next_refresh_at(&self, last_attempt: Option<u64>) -> Option<u64> {
+        let interval = self.refresh_interval_mins.filter(|_| self.enabled)?;
+        let since = self.fetched_at.max(last_attempt).unwrap_or(0);
+        Some(since + interval * 60)
+    }
+
+    /// Replace cached nodes
+    pub fn update_nodes<'a>(&mut self, nodes: impl IntoIterator<Item = &'a Node>) {
+        self.nodes = nodes.into_iter().map(SavedNode::from).collect();
+    }
+}
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub subscribe_url: Option<String>,
     #[serde(default)]
-    pub nodes: Vec<SavedNode>,
+    pub subscriptions: Vec<Subscription>,
+    /// Nodes added by hand from share links, never touched by refresh
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manual_nodes: Vec<SavedNode>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sort_column: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sort_direction: Option<String>,
+    /// Warn when less than this percentage of a subscription's quota is left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_warn_percent: Option<u64>,
+    /// Warn when a subscription expires within this many days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_warn_days: Option<u64>,
+    /// Rules for informational pseudo-nodes, built-in keywords when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info_rules: Option<Vec<InfoRule>>,
+    /// TCP-test new nodes after an automatic refresh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retest_after_refresh: Option<bool>,
+    /// Patch the xray outbound with this tag instead of writing a new config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xray_outbound_tag: Option<String>,
+    /// How xray is restarted after applying a node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<ServiceBackend>,
+    /// Number of applied xray configs kept for undo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_size: Option<usize>,
+    /// xray setups to choose from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+    /// Name of the selected profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    /// Embedded HTTP server publishing the node list to other devices
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serve: Option<ServeOptions>,
+}
+
+/// Default quota warning threshold in percent
+pub const DEFAULT_QUOTA_WARN_PERCENT: u64 = 10;
+/// Default expiry warning threshold in days
+pub const DEFAULT_EXPIRY_WARN_DAYS: u64 = 7;
+
+/// Current Unix time in seconds
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 impl Config {
@@ -124,25 +226,32 @@ impl Config {
         dirs::config_dir().map(|p| p.join("subman.json"))
     }
 
-    /// Load config from file, returns default if file doesn't exist
-    pub fn load() -> Config {
-        let Some(path) = Self::config_path() else {
-            return Config::default();
+    /// Load config from file, returns default if file doesn't exist. A file
+    /// that can't be read or parsed is an error, so it is not saved over.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let Some(path) = path else {
+            return Ok(Config::default());
         };
 
         if !path.exists() {
-            return Config::default();
+            return Ok(Config::default());
         }
 
-        match fs::read_to_string(&path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => Config::default(),
-        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))
+    }
+
+    /// Parse config content, upgrading files written by older versions
+    fn parse(content: &str) -> Result<Config> {
+        let mut value = serde_json::from_str::<serde_json::Value>(content)?;
+        migrate(&mut value);
+        Ok(serde_json::from_value(value)?)
     }
 
     /// Save config to file
-    pub fn save(&self) -> Result<()> {
-        let Some(path) = Self::config_path() else {
+    pub fn save(&self, path: Option<&Path>) -> Result<()> {
+        let Some(path) = path else {
             return Err(anyhow::anyhow!("Could not determine config directory"));
         };
 
@@ -152,17 +261,171 @@ impl Config {
         }
 
         let content = serde_json::to_string_pretty(self)?;
-        fs::write(&path, content)?;
+        fs::write(path, content)?;
         Ok(())
     }
+}
+
+/// Format a Unix timestamp as "YYYY-MM-DD HH:MM UTC"
+pub fn format_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let minutes = secs % 86400 / 60;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Format a number of seconds as a short countdown such as "1h 05m" or "4m 09s"
+pub fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Upgrade a raw config document to the current layout
+fn migrate(value: &mut serde_json::Value) {
+    // Nodes saved before multi-protocol support are raw vmess link fields (add, ps, aid...)
+    if let Some(nodes) = value.get_mut("nodes").and_then(|n| n.as_array_mut()) {
+        for saved in nodes.iter_mut() {
+            if saved.get("add").is_some() && saved.get("address").is_none() {
+                migrate_vmess_node(saved);
+            }
+        }
+    }
+
+    // Single-subscription configs kept subscribe_url and nodes at the top level
+    let Some(root) = value.as_object_mut() else {
+        return;
+    };
+    if !root.contains_key("subscriptions")
+        && (root.contains_key("subscribe_url") || root.contains_key("nodes"))
+    {
+        let url = root.remove("subscribe_url").unwrap_or_default();
+        let nodes = root.remove("nodes").unwrap_or_default();
+        root.insert(
+            "subscriptions".to_string(),
+            serde_json::json!([{
+                "name": "default",
+                "url": url.as_str().unwrap_or_default(),
+                "enabled": true,
+                "nodes": if nodes.is_array() { nodes } else { serde_json::json!([]) },
+            }]),
+        );
+    }
+}
+
+/// Convert a legacy vmess node entry to the current node layout, keeping latency data
+fn migrate_vmess_node(saved: &mut serde_json::Value) {
+    let Ok(vmess) = serde_json::from_value::<VmessNode>(saved.clone()) else {
+        return;
+    };
+    let Ok(serde_json::Value::Object(mut migrated)) = serde_json::to_value(Node::from(vmess))
+    else {
+        return;
+    };
+    for key in [
+        "http_latency",
+        "tcp_latency",
+        "http_timed_out",
+        "tcp_timed_out",
+    ] {
+        if let Some(v) = saved.get(key) {
+            migrated.insert(key.to_string(), v.clone());
+        }
+    }
+    *saved = serde_json::Value::Object(migrated);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Protocol;
 
-    /// Convert saved nodes to VmessNodes
-    pub fn to_vmess_nodes(&self) -> Vec<VmessNode> {
-        self.nodes.iter().cloned().map(VmessNode::from).collect()
+    #[test]
+    fn test_load_legacy_vmess_nodes() {
+        let content = r#"{"subscribe_url":"https://example.com/sub","nodes":[{"v":"2","ps":"Old","add":"old.test.com","port":"443","id":"uuid","aid":"0","net":"ws","type":"none","host":"","path":"/ws","tls":"tls","sni":"","alpn":"","fp":"","tcp_latency":42}]}"#;
+
+        let config = Config::parse(content).unwrap();
+        assert_eq!(config.subscriptions.len(), 1);
+        assert_eq!(config.subscriptions[0].name, "default");
+        assert_eq!(config.subscriptions[0].url, "https://example.com/sub");
+        assert!(config.subscriptions[0].enabled);
+        let nodes = config.subscriptions[0].to_nodes();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].protocol(), Protocol::Vmess);
+        assert_eq!(nodes[0].display_name(), "Old");
+        assert_eq!(nodes[0].transport.path, "/ws");
+        assert_eq!(nodes[0].tcp_latency, LatencyStatus::Success(42));
     }
 
-    /// Update nodes from VmessNodes
-    pub fn update_nodes(&mut self, nodes: &[VmessNode]) {
-        self.nodes = nodes.iter().map(SavedNode::from).collect();
+    #[test]
+    fn test_parse_invalid_config() {
+        let config = Config::parse(r#"{"serve": {"listen": ":8964", "sort": "tcp"}}"#).unwrap();
+        assert_eq!(config.serve.unwrap().listen, ":8964");
+
+        // A typo in one field must not be read as an empty config that then gets saved
+        for content in [
+            r#"{"subscriptions": [], "serve": {"sort": "fastest"}}"#,
+            r#"{"subscriptions": [], "info_rules": [{"glob": "*"}]}"#,
+            r#"{"subscriptions": ["#,
+        ] {
+            assert!(Config::parse(content).is_err(), "{content}");
+        }
+    }
+
+    #[test]
+    fn test_saved_node_roundtrip() {
+        let mut node = crate::vless::from_link(
+            "vless://uuid@1.2.3.4:443?security=reality&pbk=key&sid=ab&type=grpc&serviceName=svc#R",
+        )
+        .unwrap();
+        node.http_latency = LatencyStatus::TimedOut;
+
+        let json = serde_json::to_string(&SavedNode::from(&node)).unwrap();
+        let restored = Node::from(serde_json::from_str::<SavedNode>(&json).unwrap());
+        assert_eq!(restored.settings, node.settings);
+        assert_eq!(restored.security, node.security);
+        assert_eq!(restored.transport, node.transport);
+        assert_eq!(restored.http_latency, LatencyStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_time(951782400 + 3661), "2000-02-29 01:01 UTC");
+        assert_eq!(format_time(1791763200), "2026-10-12 00:00 UTC");
+        assert_eq!(format_duration(249), "4m 09s");
+        assert_eq!(format_duration(3900), "1h 05m");
+    }
+
+    #[test]
+    fn test_next_refresh_at() {
+        let mut subscription = Subscription::new("a", "https://a.test.com/sub");
+        assert_eq!(subscription.next_refresh_at(None), None);
+
+        subscription.refresh_interval_mins = Some(30);
+        assert_eq!(subscription.next_refresh_at(None), Some(1800));
+        subscription.fetched_at = Some(1000);
+        assert_eq!(subscription.next_refresh_at(None), Some(2800));
+        assert_eq!(subscription.next_refresh_at(Some(2000)), Some(3800));
+
+        subscription.enabled = false;
+        assert_eq!(subscription.next_refresh_at(None), None);
     }
 }
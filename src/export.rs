@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::node::Node;
+
+/// Subscription body of share links: the base64 of the newline-separated list
+pub fn subscription_body(links: &[String]) -> String {
+    general_purpose::STANDARD.encode(links.join("\n"))
+}
+
+/// Write share links to a file as a base64 subscription body
+pub fn write_subscription(path: &Path, links: &[String]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, subscription_body(links))
+        .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))
+}
+
+/// Copies of the nodes whose display names are unique and differ from the
+/// `reserved` names, appending " (2)", " (3)" ... to repeated names
+pub fn with_unique_names(nodes: &[Node], reserved: &[&str]) -> Vec<Node> {
+    let mut taken: HashSet<String> = reserved.iter().map(|r| r.to_string()).collect();
+    nodes
+        .iter()
+        .map(|node| {
+            let base = node.display_name();
+            let mut name = base.clone();
+            let mut n = 2;
+            while !taken.insert(name.clone()) {
+                name = format!("{base} ({n})");
+                n += 1;
+            }
+            let mut node = node.clone();
+            node.name = name;
+            node
+        })
+        .collect()
+}
+
+/// Expand a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Render text as a QR code of Unicode half blocks, two modules per character row
+pub fn qr_lines(text: &str) -> Result<Vec<String>> {
+    let code = QrCode::new(text.as_bytes()).map_err(|e| anyhow!("QR code error: {e}"))?;
+    let rendered = code.render::<Dense1x2>().quiet_zone(true).build();
+    Ok(rendered.lines().map(String::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::decode_base64;
+
+    #[test]
+    fn test_share_link_roundtrip() {
+        let links = [
+            "vmess://eyJ2IjoiMiIsInBzIjoiVk0iLCJhZGQiOiJ2bS50ZXN0LmNvbSIsInBvcnQiOiI0NDMiLCJpZCI6InV1aWQiLCJhaWQiOiIwIiwibmV0Ijoid3MiLCJ0eXBlIjoibm9uZSIsImhvc3QiOiJjZG4udGVzdC5jb20iLCJwYXRoIjoiL3dzIiwidGxzIjoidGxzIiwic25pIjoiY2RuLnRlc3QuY29tIn0=",
+            "vless://uuid@1.2.3.4:443?encryption=none&flow=xtls-rprx-vision&security=reality&sni=www.microsoft.com&fp=chrome&pbk=key&sid=ab&type=tcp#HK%20Reality",
+            "trojan://p%40ss@trojan.test.com:443?sni=cdn.test.com&type=grpc&serviceName=svc#JP",
+            "ss://YWVzLTI1Ni1nY206c2VjcmV0@[::1]:8388/?plugin=obfs-local%3Bobfs%3Dhttp#SS%20Node",
+            "ss://2022-blake3-aes-128-gcm:YctPZ6U7xPPcU%2Bgp3u%2B0tx%2FtRizJN9K8y%2BuKlW2qjlI%3D@1.2.3.4:8443#SS2022",
+        ];
+        for link in links {
+            let node = Node::from_link(link).unwrap();
+            let exported = node.to_link();
+            let restored = Node::from_link(&exported).unwrap();
+            assert!(restored.same_config(&node), "{link} -> {exported}");
+        }
+
+        let exported: Vec<String> = links.iter().map(|l| l.to_string()).collect();
+        let body = decode_base64(&subscription_body(&exported)).unwrap();
+        assert_eq!(String::from_utf8(body).unwrap(), exported.join("\n"));
+    }
+
+    #[test]
+    fn test_with_unique_names() {
+        let nodes: Vec<Node> = ["HK", "HK", "direct", "HK (2)", "HK"]
+            .iter()
+            .map(|name| Node::from_link(&format!("trojan://p@a.test.com:443#{name}")).unwrap())
+            .collect();
+        let names: Vec<String> = with_unique_names(&nodes, &["direct"])
+            .into_iter()
+            .map(|n| n.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec!["HK", "HK (2)", "direct (2)", "HK (2) (2)", "HK (3)"]
+        );
+    }
+
+    #[test]
+    fn test_qr_lines() {
+        let lines = qr_lines("trojan://p@a.test.com:443#A").unwrap();
+        // Square symbol with a quiet zone, two modules per line
+        let width = lines[0].chars().count();
+        assert!(width > 21);
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == width));
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cache::fnv1a;
+use crate::node::Node;
+
+/// Default number of applied configs to keep
+pub const DEFAULT_HISTORY_SIZE: usize = 10;
+
+/// An xray config that was applied, with the node it was built for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub node: Node,
+    /// Unix timestamp of when the config was applied
+    pub applied_at: u64,
+    pub config: Value,
+}
+
+/// Ring of the last applied configs of one xray config path, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Get the state directory (~/.local/state/subman)
+    pub fn state_dir() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|p| p.join("subman"))
+    }
+
+    /// History file of an xray config path
+    fn path(config_path: &str) -> Option<PathBuf> {
+        Self::state_dir().map(|dir| dir.join(format!("history-{:016x}.json", fnv1a(config_path))))
+    }
+
+    /// Load the history of an xray config path, empty if there is none
+    pub fn load(config_path: &str) -> History {
+        Self::path(config_path)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save as the history of an xray config path
+    pub fn save(&self, config_path: &str) -> Result<()> {
+        let path = Self::path(config_path)
+            .ok_or_else(|| anyhow!("Could not determine state directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record an applied config, dropping the oldest entries beyond `limit`
+    pub fn push(&mut self, entry: HistoryEntry, limit: usize) {
+        self.entries.insert(0, entry);
+        self.entries.truncate(limit.max(1));
+    }
+
+    /// Entry applied before the current one
+    pub fn previous(&self) -> Option<&HistoryEntry> {
+        self.entries.get(1)
+    }
+
+    /// Forget the entries newer than `position` after going back to it
+    pub fn rewind(&mut self, position: usize) {
+        self.entries.drain(..position.min(self.entries.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(name: &str, applied_at: u64) -> HistoryEntry {
+        let node = Node::from_link(&format!("trojan://p@{name}.test.com:443#{name}")).unwrap();
+        HistoryEntry {
+            node,
+            applied_at,
+            config: json!({ "outbounds": [] }),
+        }
+    }
+
+    #[test]
+    fn test_history_ring() {
+        let mut history = History::default();
+        assert!(history.previous().is_none());
+        for (i, name) in ["a", "b", "c", "d"].iter().enumerate() {
+            history.push(entry(name, i as u64), 3);
+        }
+        let names: Vec<&str> = history
+            .entries
+            .iter()
+            .map(|e| e.node.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["d", "c", "b"]);
+        assert_eq!(history.previous().unwrap().node.name, "c");
+
+        history.rewind(1);
+        assert_eq!(history.entries[0].node.name, "c");
+        assert_eq!(history.previous().unwrap().node.name, "b");
+
+        let restored: History =
+            serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!(restored.entries.len(), 2);
+        assert!(restored.entries[0]
+            .node
+            .same_config(&history.entries[0].node));
+
+        history.rewind(5);
+        assert!(history.entries.is_empty());
+    }
+}
@@ -6,7 +6,7 @@ use tokio::net::TcpStream;
 use tokio::process::{Child, Command};
 use tokio::sync::mpsc;
 
-use crate::vmess::{LatencyStatus, VmessNode};
+use crate::node::{LatencyStatus, Node};
 use crate::xray::save_config_to_path;
 
 const TEST_URL: &str = "https://www.google.com/generate_204";
@@ -84,7 +84,7 @@ async fn start_xray(config_path: &str, port: u16) -> Result<ProcessGuard> {
 }
 
 /// Test HTTP latency for a single node (via xray proxy)
-pub async fn test_node_http_latency(node: &VmessNode) -> LatencyStatus {
+pub async fn test_node_http_latency(node: &Node) -> LatencyStatus {
     let port = get_test_port();
     let config_path = format!("/tmp/xray_test_{port}.json");
 
@@ -133,13 +133,14 @@ pub async fn test_node_http_latency(node: &VmessNode) -> LatencyStatus {
 }
 
 /// Test TCP connection latency for a single node (direct connection to node's address)
-pub async fn test_node_tcp_latency(node: &VmessNode) -> LatencyStatus {
-    let addr = format!("{}:{}", node.add, node.get_port());
+pub async fn test_node_tcp_latency(node: &Node) -> LatencyStatus {
+    // A (host, port) pair also works for IPv6 addresses, stored without brackets
+    let addr = (node.address.as_str(), node.port);
 
     let start = Instant::now();
     let result = tokio::time::timeout(
         Duration::from_secs(TEST_TIMEOUT_SECS),
-        TcpStream::connect(&addr),
+        TcpStream::connect(addr),
     )
     .await;
 
@@ -156,10 +157,10 @@ pub struct LatencyResult {
     pub test_type: TestType,
 }
 
-/// Test latency for all nodes in parallel
+/// Test latency for all nodes in parallel, reporting results by the given indices
 /// Returns the cancel flag that can be used to stop the test
 pub async fn test_all_latencies(
-    nodes: Vec<VmessNode>,
+    nodes: Vec<(usize, Node)>,
     result_tx: mpsc::Sender<LatencyResult>,
     max_concurrent: usize,
     test_type: TestType,
@@ -170,7 +171,7 @@ pub async fn test_all_latencies(
     let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrent));
     let mut handles = Vec::new();
 
-    for (index, node) in nodes.into_iter().enumerate() {
+    for (index, node) in nodes {
         // Check if cancelled before starting new test
         if cancel_flag.load(Ordering::SeqCst) {
             break;
@@ -227,4 +228,19 @@ mod tests {
         reset_port_counter();
         assert_eq!(get_test_port(), 10800);
     }
+
+    #[tokio::test]
+    async fn test_tcp_latency_ipv6() {
+        let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await else {
+            // No IPv6 loopback in this environment
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+        let node = Node::from_link(&format!("trojan://p@[::1]:{port}#v6")).unwrap();
+        assert_eq!(node.address, "::1");
+        assert!(matches!(
+            test_node_tcp_latency(&node).await,
+            LatencyStatus::Success(_)
+        ));
+    }
 }
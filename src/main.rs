@@ -1,7 +1,23 @@
 mod app;
+mod cache;
+mod clash;
 mod config;
+mod export;
+mod history;
 mod latency;
+mod merge;
+mod node;
+mod notice;
+mod serve;
+mod service;
+mod shadowsocks;
+mod singbox;
+mod source;
 mod subscribe;
+mod template;
+mod trojan;
+mod userinfo;
+mod vless;
 mod vmess;
 mod xray;
 
@@ -9,7 +25,8 @@ use anyhow::Result;
 use clap::Parser;
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEventKind, KeyModifiers,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -26,13 +43,17 @@ use std::io;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
-use app::{App, SortColumn, SortDirection};
+use app::{App, ExportView, InputForm, SortColumn, SortDirection};
+use config::{format_duration, format_time, unix_time, Subscription};
 use latency::{test_all_latencies, LatencyResult, TestType};
-use subscribe::fetch_subscription;
-use vmess::LatencyStatus;
-use xray::{restart_xray_service, save_config_with_path, DEFAULT_XRAY_CONFIG_PATH};
-
-/// Subscription Manager - A TUI tool for managing vmess nodes
+use subscribe::{fetch_all, FetchOutcome, FetchTarget, IssueKind, ParseReport};
+use node::{LatencyStatus, Node};
+use serve::NodeFilter;
+use service::ServiceBackend;
+use template::template_config;
+use xray::{apply_config, build_config, check_supported, DEFAULT_XRAY_CONFIG_PATH};
+
+/// Subscription Manager - A TUI tool for managing proxy subscription nodes
 #[derive(Parser)]
 #[command(name = "subman")]
 #[command(about = "A TUI tool for managing vmess subscription nodes")]
@@ -44,6 +65,23 @@ struct Args {
     /// Path to xray config file
     #[arg(short, long, default_value = DEFAULT_XRAY_CONFIG_PATH)]
     config: String,
+
+    /// Replace only the outbound with this tag in the existing xray config
+    #[arg(long, value_name = "TAG")]
+    outbound_tag: Option<String>,
+
+    /// How to restart xray: pgrep, systemd[:UNIT], systemd-reload[:UNIT],
+    /// openrc[:SERVICE], openrc-reload[:SERVICE], pidfile:PATH or command:CMD
+    #[arg(long, value_name = "SPEC")]
+    service: Option<ServiceBackend>,
+
+    /// Select this profile from the config
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Serve the node list as subscriptions on this address, e.g. 0.0.0.0:8964
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<String>,
 }
 
 /// Pad a string to target width, accounting for wide characters (CJK)
@@ -62,6 +100,23 @@ fn display_width(s: &str) -> usize {
     s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
 }
 
+/// Keep the end of a string that fits the width, marking cut text with "…"
+fn tail_to_width(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let mut tail: Vec<char> = Vec::new();
+    let mut used = 1;
+    for c in s.chars().rev() {
+        used += display_width(c.encode_utf8(&mut [0; 4]));
+        if used > width {
+            break;
+        }
+        tail.push(c);
+    }
+    std::iter::once('…').chain(tail.into_iter().rev()).collect()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -69,12 +124,26 @@ async fn main() -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Create app and run
-    let mut app = App::new(args.parallel, args.config);
+    let mut app = App::new(args.parallel, args.config, args.outbound_tag);
+    if let Some(service) = args.service {
+        app.service = service;
+    }
+    if let Some(name) = &args.profile {
+        if let Err(e) = app.select_profile(name) {
+            app.set_error(e.to_string());
+        }
+    }
+    start_server(&mut app, args.serve).await;
     let result = run_app(&mut terminal, &mut app).await;
 
     // Restore terminal
@@ -82,7 +151,8 @@ async fn main() -> Result<()> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
@@ -93,12 +163,43 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Start the subscription server when configured or requested with --serve
+async fn start_server(app: &mut App, listen: Option<String>) {
+    let mut options = app.serve_options.clone().unwrap_or_default();
+    if let Some(listen) = listen {
+        options.listen = listen;
+    }
+    if options.listen.is_empty() {
+        return;
+    }
+    let started = async {
+        let filter = NodeFilter::compile(&options)?;
+        let listener = serve::bind(&options).await?;
+        anyhow::Ok((filter, listener))
+    };
+    match started.await {
+        Ok((filter, listener)) => {
+            let addr = listener
+                .local_addr()
+                .map_or(options.listen.clone(), |a| a.to_string());
+            let nodes = app.served_nodes.clone();
+            tokio::spawn(serve::serve(listener, nodes, filter, options.token));
+            app.set_status(format!(
+                "Serving /sub, /clash and /singbox on http://{addr}"
+            ));
+        }
+        Err(e) => app.set_error(e.to_string()),
+    }
+}
+
 async fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
 ) -> Result<()> {
     // Channel for receiving latency results
     let (latency_tx, mut latency_rx) = mpsc::channel::<LatencyResult>(100);
+    // Channel for receiving refresh results, flagged when the refresh was automatic
+    let (refresh_tx, mut refresh_rx) = mpsc::channel::<(Vec<FetchOutcome>, bool)>(1);
 
     loop {
         // Draw UI
@@ -108,23 +209,35 @@ async fn run_app<B: ratatui::backend::Backend>(
         while let Ok(result) = latency_rx.try_recv() {
             let test_type = result.test_type;
             app.update_latency(result);
+            // Shadowsocks plugin nodes are left out of HTTP tests and stay untested
+            let unsupported = match test_type {
+                TestType::Http => app
+                    .sorted_nodes
+                    .iter()
+                    .filter(|n| check_supported(&n.node).is_err())
+                    .count(),
+                TestType::Tcp => 0,
+            };
             let tested = app
                 .sorted_nodes
                 .iter()
-                .filter(|n| match test_type {
-                    TestType::Http => n.node.http_latency.is_tested(),
-                    TestType::Tcp => n.node.tcp_latency.is_tested(),
-                })
+                .filter(|n| n.node.latency(test_type).is_tested())
                 .count();
-            let total = app.sorted_nodes.len();
-            if tested == total {
+            let total = app.sorted_nodes.len() - unsupported;
+            if tested >= total {
                 app.testing = false;
                 app.current_test_type = None;
                 let type_name = match test_type {
                     TestType::Http => "HTTP",
                     TestType::Tcp => "TCP",
                 };
-                app.set_status(format!("{type_name} latency test completed"));
+                if unsupported > 0 {
+                    app.set_status(format!(
+                        "{type_name} latency test completed ({unsupported} shadowsocks plugin nodes not tested: xray cannot run SIP003 plugins)"
+                    ));
+                } else {
+                    app.set_status(format!("{type_name} latency test completed"));
+                }
                 app.resort();
                 app.save_config();
             } else {
@@ -138,9 +251,49 @@ async fn run_app<B: ratatui::backend::Backend>(
             }
         }
 
+        // Merge a finished scheduled refresh
+        if let Ok((outcomes, automatic)) = refresh_rx.try_recv() {
+            app.refreshing = false;
+            app.apply_refresh(outcomes);
+            if automatic && app.retest_after_refresh && !app.testing {
+                let nodes = app.untested_nodes(TestType::Tcp);
+                if !nodes.is_empty() {
+                    start_latency_test(app, &latency_tx, TestType::Tcp, nodes);
+                }
+            }
+        }
+
+        // Start scheduled refreshes and reloads of changed watched files in the
+        // background; wait while a test runs or the subscription list is being
+        // edited since results are keyed by index
+        if !app.refreshing && !app.testing && !app.show_subscriptions && app.input.is_none() {
+            let now = unix_time();
+            let mut targets = app.due_refresh_targets(now);
+            for target in app.changed_sources(now) {
+                if !targets.iter().any(|t| t.index == target.index) {
+                    targets.push(target);
+                }
+            }
+            if !targets.is_empty() {
+                app.set_status(format!(
+                    "Auto-refreshing {} subscription(s)...",
+                    targets.len()
+                ));
+                spawn_refresh(app, &refresh_tx, targets, true);
+            }
+        }
+
         // Poll for events with timeout
         if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            // Pasted share links arrive as one event instead of keystrokes
+            if let Event::Paste(text) = &event {
+                if let Some(form) = app.input.as_mut() {
+                    form.paste(text);
+                }
+                continue;
+            }
+            if let Event::Key(key) = event {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
@@ -151,26 +304,114 @@ async fn run_app<B: ratatui::backend::Backend>(
                     continue;
                 }
 
-                // Handle input mode
-                if app.input_mode {
+                // Handle input form
+                if let Some(form) = app.input.as_mut() {
                     match key.code {
                         KeyCode::Enter => {
                             app.confirm_input();
                         }
                         KeyCode::Esc => {
-                            app.exit_input_mode();
+                            app.cancel_input();
+                        }
+                        KeyCode::Tab | KeyCode::Down => form.focus_next(),
+                        KeyCode::BackTab | KeyCode::Up => form.focus_previous(),
+                        KeyCode::Char(c) => form.push(c),
+                        KeyCode::Backspace => form.pop(),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Handle parse report popup
+                if app.show_report {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => app.scroll_report_up(1),
+                        KeyCode::Down | KeyCode::Char('j') => app.scroll_report_down(1),
+                        KeyCode::PageUp => app.scroll_report_up(10),
+                        KeyCode::PageDown => app.scroll_report_down(10),
+                        KeyCode::Esc
+                        | KeyCode::Char('q')
+                        | KeyCode::Char('p')
+                        | KeyCode::Char('P') => {
+                            app.close_report();
                         }
-                        KeyCode::Char(c) => {
-                            app.input_buffer.push(c);
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Handle history popup
+                if app.show_history {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => app.history_up(),
+                        KeyCode::Down | KeyCode::Char('j') => app.history_down(),
+                        KeyCode::Enter if !app.refreshing => {
+                            let position = app.history_selected;
+                            app.close_history();
+                            revert_to(terminal, app, position)?;
                         }
-                        KeyCode::Backspace => {
-                            app.input_buffer.pop();
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('H') => {
+                            app.close_history();
                         }
                         _ => {}
                     }
                     continue;
                 }
 
+                // Handle export popup
+                if app.export.is_some() {
+                    match key.code {
+                        KeyCode::Left | KeyCode::Char('h') => app.previous_export_link(),
+                        KeyCode::Right | KeyCode::Char('l') => app.next_export_link(),
+                        KeyCode::Char('w') => app.export_to_file(),
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('x') => {
+                            app.close_export();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Handle subscription removal confirmation - y or Enter removes
+                if app.pending_removal.is_some() {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                            app.remove_subscription()
+                        }
+                        _ => app.cancel_remove_subscription(),
+                    }
+                    continue;
+                }
+
+                // Handle subscription manager screen
+                if app.show_subscriptions {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => app.select_previous_subscription(),
+                        KeyCode::Down | KeyCode::Char('j') => app.select_next_subscription(),
+                        // Refresh results are keyed by index: keep the list as is meanwhile
+                        KeyCode::Char('K') if !app.refreshing => app.move_subscription(true),
+                        KeyCode::Char('J') if !app.refreshing => app.move_subscription(false),
+                        KeyCode::Char('a') if !app.refreshing => app.add_subscription(),
+                        KeyCode::Char('e') | KeyCode::Enter if !app.refreshing => {
+                            app.edit_subscription()
+                        }
+                        KeyCode::Char('d') | KeyCode::Delete if !app.refreshing => {
+                            app.ask_remove_subscription()
+                        }
+                        KeyCode::Char(' ') if !app.refreshing => app.toggle_subscription(),
+                        KeyCode::Char('r') if !app.refreshing => {
+                            let selected = app.subscription_selected;
+                            refresh_subscriptions(app, &refresh_tx, Some(selected));
+                        }
+                        KeyCode::Esc
+                        | KeyCode::Char('q')
+                        | KeyCode::Char('u')
+                        | KeyCode::Char('U') => app.close_subscriptions(),
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 // Handle Ctrl+C
                 if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
                 {
@@ -200,108 +441,73 @@ async fn run_app<B: ratatui::backend::Backend>(
                         // Toggle sort direction
                         app.toggle_sort_direction();
                     }
-                    KeyCode::Char('u') | KeyCode::Char('U') => {
-                        if !app.testing && !app.refreshing {
-                            app.enter_input_mode();
-                        }
+                    KeyCode::Char('u') | KeyCode::Char('U') if !app.testing && !app.refreshing => {
+                        app.open_subscriptions();
+                    }
+                    KeyCode::Char('r') | KeyCode::Char('R') if !app.refreshing && !app.testing => {
+                        refresh_subscriptions(app, &refresh_tx, None);
+                    }
+                    KeyCode::Char('p') | KeyCode::Char('P') => {
+                        app.open_report();
+                    }
+                    KeyCode::Char(' ') => {
+                        app.toggle_mark();
+                    }
+                    KeyCode::Char('x') => {
+                        app.open_export();
+                    }
+                    KeyCode::Char('o') => {
+                        app.cycle_profile();
                     }
-                    KeyCode::Char('r') | KeyCode::Char('R') => {
-                        if !app.refreshing && !app.testing {
-                            if let Some(url) = app.subscribe_url.clone() {
-                                app.refreshing = true;
-                                // Clear list first
-                                app.clear_nodes();
-                                app.set_status("Refreshing subscription...");
-                                terminal.draw(|f| ui(f, app))?;
-
-                                match fetch_subscription(&url).await {
-                                    Ok(nodes) => {
-                                        let count = nodes.len();
-                                        app.set_nodes(nodes);
-                                        app.save_config();
-                                        app.set_status(format!(
-                                            "Loaded {count} nodes. Press t for TCP, T for HTTP test."
-                                        ));
-                                    }
-                                    Err(e) => {
-                                        app.set_error(format!("{e}"));
-                                        app.set_status("Failed to refresh subscription");
-                                    }
-                                }
-                                app.refreshing = false;
-                            } else {
-                                app.set_status("No subscription URL. Press U to set one.");
-                            }
+                    KeyCode::Char('H') => {
+                        app.open_history();
+                    }
+                    KeyCode::Char('z') if !app.refreshing => {
+                        if app.history.previous().is_some() {
+                            revert_to(terminal, app, 1)?;
+                        } else {
+                            app.set_status("Nothing to undo");
                         }
                     }
-                    KeyCode::Char('t') => {
+                    KeyCode::Char('a') if !app.testing && !app.refreshing => {
+                        app.add_manual_nodes();
+                    }
+                    KeyCode::Char('d') | KeyCode::Delete if !app.testing && !app.refreshing => {
+                        app.remove_selected_manual_node();
+                    }
+                    KeyCode::Char('t')
+                        if !app.testing && !app.refreshing && !app.sorted_nodes.is_empty() =>
+                    {
                         // TCP test (lowercase t)
-                        if !app.testing && !app.refreshing && !app.sorted_nodes.is_empty() {
-                            app.testing = true;
-                            app.current_test_type = Some(TestType::Tcp);
-                            app.clear_tcp_latencies();
-                            app.set_status("Starting TCP latency test...");
-
-                            let nodes = app.get_nodes_clone();
-                            let tx = latency_tx.clone();
-                            let parallel = app.parallel_count;
-                            let cancel_flag = app.get_cancel_flag();
-
-                            tokio::spawn(async move {
-                                test_all_latencies(nodes, tx, parallel, TestType::Tcp, cancel_flag)
-                                    .await;
-                            });
-                        }
+                        app.clear_tcp_latencies();
+                        app.set_status("Starting TCP latency test...");
+                        let nodes = app.get_nodes_clone().into_iter().enumerate().collect();
+                        start_latency_test(app, &latency_tx, TestType::Tcp, nodes);
                     }
-                    KeyCode::Char('T') => {
+                    KeyCode::Char('T')
+                        if !app.testing && !app.refreshing && !app.sorted_nodes.is_empty() =>
+                    {
                         // HTTP test (uppercase T)
-                        if !app.testing && !app.refreshing && !app.sorted_nodes.is_empty() {
-                            app.testing = true;
-                            app.current_test_type = Some(TestType::Http);
-                            app.clear_http_latencies();
+                        app.clear_http_latencies();
+                        let nodes = app.http_test_nodes();
+                        if nodes.is_empty() {
+                            app.set_status(
+                                "No nodes to HTTP-test: xray cannot run SIP003 shadowsocks plugins",
+                            );
+                        } else {
                             app.set_status("Starting HTTP latency test...");
-
-                            let nodes = app.get_nodes_clone();
-                            let tx = latency_tx.clone();
-                            let parallel = app.parallel_count;
-                            let cancel_flag = app.get_cancel_flag();
-
-                            tokio::spawn(async move {
-                                test_all_latencies(nodes, tx, parallel, TestType::Http, cancel_flag)
-                                    .await;
-                            });
+                            start_latency_test(app, &latency_tx, TestType::Http, nodes);
                         }
                     }
-                    KeyCode::Enter => {
-                        if !app.refreshing {
-                            if let (Some(node), Some(original_index)) =
-                                (app.selected_node().cloned(), app.selected_original_index())
-                            {
-                                let node_name = node.display_name();
-                                app.set_status(format!("Applying {node_name}..."));
-                                terminal.draw(|f| ui(f, app))?;
-
-                                let config_path = app.xray_config_path.clone();
-                                match save_config_with_path(&node, &config_path) {
-                                    Ok(()) => match restart_xray_service() {
-                                        Ok(result) => {
-                                            app.set_active_node(original_index);
-                                            app.set_status(format!(
-                                                "Applied {node_name} - xray restarted (PID: {} -> {})",
-                                                result.old_pid, result.new_pid
-                                            ));
-                                        }
-                                        Err(e) => {
-                                            app.set_status(format!(
-                                                "Config saved but failed to restart xray: {e}"
-                                            ));
-                                        }
-                                    },
-                                    Err(e) => {
-                                        app.set_status(format!("Failed to save config: {e}"));
-                                    }
-                                }
-                            }
+                    KeyCode::Enter if !app.refreshing => {
+                        if let (Some(node), Some(original_index)) =
+                            (app.selected_node().cloned(), app.selected_original_index())
+                        {
+                            let node_name = node.display_name();
+                            app.set_status(format!("Applying {node_name}..."));
+                            terminal.draw(|f| ui(f, app))?;
+
+                            apply_node(app, &node, original_index);
                         }
                     }
                     _ => {}
@@ -317,6 +523,111 @@ async fn run_app<B: ratatui::backend::Backend>(
     Ok(())
 }
 
+/// Write the config for a node and reload xray, reporting the outcome
+fn apply_node(app: &mut App, node: &Node, original_index: usize) {
+    let node_name = node.display_name();
+    if let Err(e) = check_supported(node) {
+        app.set_error(e.to_string());
+        app.set_status(format!("Cannot apply {node_name}"));
+        return;
+    }
+    let template = app.active_template();
+    let config = match &template {
+        Some(path) => template_config(node, path),
+        None => build_config(
+            node,
+            &app.xray_config_path,
+            app.xray_outbound_tag.as_deref(),
+        ),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) if template.is_some() => {
+            app.set_error(format!("Template error: {e}"));
+            app.set_status("Failed to apply template");
+            return;
+        }
+        Err(e) => {
+            app.set_error(format!("Failed to build config: {e}"));
+            app.set_status(format!("Failed to apply {node_name}"));
+            return;
+        }
+    };
+
+    match apply_config(&config, &app.xray_config_path, &app.service) {
+        Ok(summary) => {
+            app.set_active_node(original_index);
+            app.record_applied(node, config);
+            app.set_status(format!("Applied {node_name} - {summary}"));
+        }
+        Err(e) => {
+            app.set_error(e.to_string());
+            app.set_status(format!("Failed to apply {node_name}"));
+        }
+    }
+}
+
+/// Re-apply the history entry at `position` and forget the newer entries
+fn revert_to<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    position: usize,
+) -> Result<()> {
+    let Some(entry) = app.history.entries.get(position).cloned() else {
+        return Ok(());
+    };
+    let node_name = entry.node.display_name();
+    app.set_status(format!("Reverting to {node_name}..."));
+    terminal.draw(|f| ui(f, app))?;
+
+    match apply_config(&entry.config, &app.xray_config_path, &app.service) {
+        Ok(summary) => {
+            app.rewind_history(position);
+            if let Some(index) = app.node_index(&entry.node) {
+                app.set_active_node(index);
+            }
+            app.set_status(format!("Reverted to {node_name} - {summary}"));
+        }
+        Err(e) => {
+            app.set_error(e.to_string());
+            app.set_status(format!("Failed to revert to {node_name}"));
+        }
+    }
+    Ok(())
+}
+
+/// Fetch one subscription (or every enabled one) concurrently and store the nodes
+/// Refresh the subscription at `only`, or every enabled one, in the background
+fn refresh_subscriptions(
+    app: &mut App,
+    refresh_tx: &mpsc::Sender<(Vec<FetchOutcome>, bool)>,
+    only: Option<usize>,
+) {
+    let targets = app.refresh_targets(only);
+    if targets.is_empty() {
+        app.set_status("No enabled subscriptions. Press U to add one.");
+        return;
+    }
+    app.set_status(format!("Refreshing {} subscription(s)...", targets.len()));
+    spawn_refresh(app, refresh_tx, targets, false);
+}
+
+/// Fetch subscriptions concurrently in a background task; the outcomes
+/// arrive on `refresh_tx` and are merged by the event loop
+fn spawn_refresh(
+    app: &mut App,
+    refresh_tx: &mpsc::Sender<(Vec<FetchOutcome>, bool)>,
+    targets: Vec<FetchTarget>,
+    automatic: bool,
+) {
+    app.refreshing = true;
+    let rules = app.info_rules.clone();
+    let tx = refresh_tx.clone();
+    tokio::spawn(async move {
+        let _ = tx.send((fetch_all(targets, &rules).await, automatic)).await;
+    });
+}
+
 fn ui(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -335,6 +646,14 @@ fn ui(f: &mut Frame, app: &App) {
         .unwrap_or(10)
         .max(10); // Minimum width of 10
 
+    let source_max_width = app
+        .subscriptions
+        .iter()
+        .map(|s| display_width(&s.name))
+        .max()
+        .unwrap_or(6)
+        .max(6);
+
     let addr_max_width = 25;
 
     // Sort indicator for column
@@ -350,7 +669,7 @@ fn ui(f: &mut Frame, app: &App) {
     };
 
     // Build header row - format matches data rows exactly
-    // Data row format: marker(2) + name(width) + "  " + addr(width) + "  " + port(5) + "  " + tcp(8) + "  " + http(8)
+    // Data row format: marker(2) + name(width) + "  " + source(width) + "  " + type(6) + "  " + addr(width) + "  " + port(5) + "  " + tcp(8) + "  " + http(8)
     let header_style = Style::default()
         .fg(Color::Cyan)
         .add_modifier(Modifier::BOLD);
@@ -358,11 +677,13 @@ fn ui(f: &mut Frame, app: &App) {
     let header = Line::from(vec![
         // Marker column - use for Name sort indicator
         Span::styled(format!("{} ", sort_indicator(SortColumn::Name)), header_style),
-        // Name + gap + Address + gap + Port + gap
+        // Name + gap + Source + gap + Type + gap + Address + gap + Port + gap
         Span::styled(
             format!(
-                "{}  {}  {:>5}  ",
+                "{}  {}  {:<6}  {}  {:>5}  ",
                 pad_string("Name", name_max_width),
+                pad_string("Source", source_max_width),
+                "Type",
                 pad_string("Address", addr_max_width),
                 "Port"
             ),
@@ -382,8 +703,10 @@ fn ui(f: &mut Frame, app: &App) {
         let node = &indexed.node;
         let original_index = indexed.original_index;
         let name = node.display_name();
-        let addr = &node.add;
-        let port = node.get_port();
+        let source = pad_string(app.source_name(indexed.source), source_max_width);
+        let protocol = node.protocol().label();
+        let addr = &node.address;
+        let port = node.port;
 
         // Check if this is the active node
         let is_active = app.active_node_index == Some(original_index);
@@ -404,8 +727,16 @@ fn ui(f: &mut Frame, app: &App) {
         let padded_name = pad_string(&name, name_max_width);
         let padded_addr = pad_string(addr, addr_max_width);
 
-        // Active node marker
-        let marker = if is_active { "● " } else { "  " };
+        // Active node marker, followed by the export mark
+        let marker = format!(
+            "{}{}",
+            if is_active { "●" } else { " " },
+            if app.marked.contains(&original_index) {
+                "+"
+            } else {
+                " "
+            }
+        );
 
         let base_style = if i == app.selected {
             Style::default()
@@ -428,7 +759,7 @@ fn ui(f: &mut Frame, app: &App) {
                 },
             ),
             Span::styled(
-                format!("{padded_name}  {padded_addr}  {port:>5}  "),
+                format!("{padded_name}  {source}  {protocol:<6}  {padded_addr}  {port:>5}  "),
                 base_style,
             ),
             Span::styled(format!("{tcp_text:>8}"), tcp_style.patch(base_style)),
@@ -439,13 +770,29 @@ fn ui(f: &mut Frame, app: &App) {
         ListItem::new(line)
     }));
 
+    // Show quota and expiry of the selected node's subscription next to the title
+    let mut list_block = Block::default()
+        .title(" Subscription Manager ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    if let Some(userinfo) = app.selected_userinfo_summary() {
+        list_block = list_block.title(
+            Line::from(format!(" {userinfo} "))
+                .style(Style::default().fg(Color::Green))
+                .right_aligned(),
+        );
+    }
+    // Informational entries the provider put in the link list
+    let notices = app.selected_notices();
+    if !notices.is_empty() {
+        list_block = list_block.title_bottom(
+            Line::from(format!(" {} ", notices.join(" · ")))
+                .style(Style::default().fg(Color::Magenta)),
+        );
+    }
+
     let list = List::new(items)
-        .block(
-            Block::default()
-                .title(" Subscription Manager ")
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Cyan)),
-        )
+        .block(list_block)
         .highlight_style(Style::default().bg(Color::DarkGray));
 
     // Use ListState to track selection (offset by 1 for header)
@@ -458,11 +805,46 @@ fn ui(f: &mut Frame, app: &App) {
     let help_text = if app.testing {
         " Ctrl+C:Cancel Test "
     } else {
-        " ↑↓:Select  Enter:Apply  R:Refresh  t:TCP  T:HTTP  s:Sort  S:Reverse  U:URL  Q:Quit "
+        " ↑↓:Select  Enter:Apply  R:Refresh  P:Report  t:TCP  T:HTTP  s:Sort  S:Reverse  U:Subs  a/d:Add/Del Node  Space:Mark  x:Export  o:Profile  H:History  z:Undo  Q:Quit "
     };
-    let status_block = Block::default()
+    let mut status_block = Block::default()
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Cyan));
+    let warnings = app.subscription_warnings();
+    if !warnings.is_empty() {
+        status_block = status_block.title(Span::styled(
+            format!(" ⚠ {} ", warnings.join(" | ")),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    let now = unix_time();
+    if let Some(secs) = app.next_auto_refresh(now) {
+        let last = app
+            .last_refreshed()
+            .map(|at| format!("refreshed {} · ", format_time(at)))
+            .unwrap_or_default();
+        let next = if app.refreshing {
+            "refreshing...".to_string()
+        } else {
+            format!("next in {}", format_duration(secs))
+        };
+        status_block = status_block.title(
+            Line::from(format!(" {last}{next} "))
+                .style(Style::default().fg(Color::DarkGray))
+                .right_aligned(),
+        );
+    }
+
+    if let Some(name) = app.active_profile_name() {
+        status_block = status_block.title_bottom(
+            Line::from(format!(" profile: {name} "))
+                .style(Style::default().fg(Color::DarkGray))
+                .right_aligned(),
+        );
+    }
 
     let inner = status_block.inner(chunks[1]);
     f.render_widget(status_block, chunks[1]);
@@ -480,36 +862,34 @@ fn ui(f: &mut Frame, app: &App) {
         .alignment(ratatui::layout::Alignment::Right);
     f.render_widget(help, status_chunks[1]);
 
-    // Input dialog
-    if app.input_mode {
-        let area = f.area();
-        let dialog_width = 60.min(area.width.saturating_sub(4));
-        let dialog_height = 5;
-        let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
-        let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
-
-        let dialog_area =
-            ratatui::layout::Rect::new(dialog_x, dialog_y, dialog_width, dialog_height);
+    // Subscription manager
+    if app.show_subscriptions {
+        render_subscriptions(f, app);
+    }
 
-        f.render_widget(Clear, dialog_area);
+    // Subscription removal confirmation
+    if let Some(subscription) = app.pending_removal.and_then(|i| app.subscriptions.get(i)) {
+        render_remove_confirmation(f, subscription);
+    }
 
-        let input_block = Block::default()
-            .title(" Enter Subscription URL (Enter to confirm, Esc to cancel) ")
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Yellow));
+    // Input dialog
+    if let Some(form) = &app.input {
+        render_input_form(f, form);
+    }
 
-        let inner_area = input_block.inner(dialog_area);
-        f.render_widget(input_block, dialog_area);
+    // Parse report dialog
+    if app.show_report {
+        render_report(f, &app.parse_reports, app.report_scroll);
+    }
 
-        let input =
-            Paragraph::new(app.input_buffer.as_str()).style(Style::default().fg(Color::White));
-        f.render_widget(input, inner_area);
+    // History dialog
+    if app.show_history {
+        render_history(f, app);
+    }
 
-        // Show cursor at end of input
-        f.set_cursor_position((
-            inner_area.x + app.input_buffer.len() as u16,
-            inner_area.y,
-        ));
+    // Export dialog
+    if let Some(export) = &app.export {
+        render_export(f, export);
     }
 
     // Error dialog
@@ -539,3 +919,316 @@ fn ui(f: &mut Frame, app: &App) {
         f.render_widget(error_text, inner_area);
     }
 }
+
+/// Start a background latency test of nodes keyed by their original index
+fn start_latency_test(
+    app: &mut App,
+    latency_tx: &mpsc::Sender<LatencyResult>,
+    test_type: TestType,
+    nodes: Vec<(usize, Node)>,
+) {
+    app.testing = true;
+    app.current_test_type = Some(test_type);
+
+    let tx = latency_tx.clone();
+    let parallel = app.parallel_count;
+    let cancel_flag = app.get_cancel_flag();
+
+    tokio::spawn(async move {
+        test_all_latencies(nodes, tx, parallel, test_type, cancel_flag).await;
+    });
+}
+
+/// Center a dialog of the given size in the frame
+fn dialog_rect(f: &Frame, width: u16, height: u16) -> ratatui::layout::Rect {
+    let area = f.area();
+    let dialog_width = width.min(area.width.saturating_sub(4));
+    let dialog_height = height.min(area.height.saturating_sub(2));
+    let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+    let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+    ratatui::layout::Rect::new(dialog_x, dialog_y, dialog_width, dialog_height)
+}
+
+/// Render the subscription manager screen
+fn render_subscriptions(f: &mut Frame, app: &App) {
+    let dialog_area = dialog_rect(f, 90, 16);
+    f.render_widget(Clear, dialog_area);
+
+    let block = Block::default()
+        .title(" Subscriptions ")
+        .title_bottom(" a:Add  e:Edit  d:Delete  Space:Enable  J/K:Move  r:Refresh  Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let name_width = app
+        .subscriptions
+        .iter()
+        .map(|s| display_width(&s.name))
+        .max()
+        .unwrap_or(4)
+        .max(4);
+
+    let now = unix_time();
+    let items: Vec<ListItem> = if app.subscriptions.is_empty() {
+        vec![ListItem::new("No subscriptions. Press a to add one.")]
+    } else {
+        app.subscriptions
+            .iter()
+            .map(|subscription| {
+                let checkbox = if subscription.enabled { "[x]" } else { "[ ]" };
+                let style = if subscription.enabled {
+                    Style::default()
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                let quota = subscription
+                    .userinfo
+                    .map(|u| format!("  [{}]", u.summary(now)))
+                    .unwrap_or_default();
+                let via = subscription
+                    .fetched_via
+                    .as_ref()
+                    .map(|mode| format!("  via {mode}"))
+                    .unwrap_or_default();
+                let interval = subscription
+                    .refresh_interval_mins
+                    .map(|mins| format!("  every {mins}m"))
+                    .unwrap_or_default();
+                let stale = subscription
+                    .stale_since
+                    .map(|since| format!("  stale since {}", format_time(since)))
+                    .unwrap_or_default();
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!(
+                            "{checkbox} {}  {:>4} nodes  {}{quota}{via}{interval}",
+                            pad_string(&subscription.name, name_width),
+                            subscription.nodes.len(),
+                            subscription.url
+                        ),
+                        style,
+                    ),
+                    Span::styled(stale, Style::default().fg(Color::Yellow)),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let mut list_state = ListState::default();
+    if !app.subscriptions.is_empty() {
+        list_state.select(Some(app.subscription_selected));
+    }
+    f.render_stateful_widget(list, dialog_area, &mut list_state);
+}
+
+/// Render the question whether to remove a subscription
+fn render_remove_confirmation(f: &mut Frame, subscription: &Subscription) {
+    let dialog_area = dialog_rect(f, 60, 5);
+    f.render_widget(Clear, dialog_area);
+
+    let block = Block::default()
+        .title(" Remove Subscription ")
+        .title_bottom(" y/Enter:Remove  any other key:Cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+    let text = Paragraph::new(format!(
+        "Remove {} and its {} nodes?",
+        subscription.name,
+        subscription.nodes.len()
+    ))
+    .block(block)
+    .wrap(ratatui::widgets::Wrap { trim: true });
+    f.render_widget(text, dialog_area);
+}
+
+/// Render a text input form with one line per field
+fn render_input_form(f: &mut Frame, form: &InputForm) {
+    let dialog_area = dialog_rect(f, 70, form.fields.len() as u16 + 2);
+    f.render_widget(Clear, dialog_area);
+
+    let input_block = Block::default()
+        .title(format!(
+            " {} (Tab to switch, Enter to confirm, Esc to cancel) ",
+            form.title()
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let inner_area = input_block.inner(dialog_area);
+    f.render_widget(input_block, dialog_area);
+
+    let label_width = form
+        .fields
+        .iter()
+        .map(|field| field.label.len())
+        .max()
+        .unwrap_or(0);
+    // Leave room for the cursor after the value
+    let value_width = (inner_area.width as usize).saturating_sub(label_width + 3);
+
+    let lines: Vec<Line> = form
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let label_style = if i == form.focus {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            Line::from(vec![
+                Span::styled(format!("{:>label_width$}: ", field.label), label_style),
+                Span::styled(
+                    tail_to_width(&field.value, value_width),
+                    Style::default().fg(Color::White),
+                ),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner_area);
+
+    // Show cursor at end of the focused field
+    let focused = tail_to_width(&form.fields[form.focus].value, value_width);
+    f.set_cursor_position((
+        inner_area.x + (label_width + 2 + display_width(&focused)) as u16,
+        inner_area.y + form.focus as u16,
+    ));
+}
+
+/// Render the export popup: the current link as a QR code followed by the link text
+fn render_export(f: &mut Frame, export: &ExportView) {
+    let Some((name, link)) = export.links.get(export.current) else {
+        return;
+    };
+    let qr_width = export.qr.first().map_or(0, |l| l.chars().count());
+    let inner_width = link.len().clamp(40, 80).max(qr_width);
+    let link_rows = link.len().div_ceil(inner_width);
+    let dialog_area = dialog_rect(
+        f,
+        inner_width as u16 + 2,
+        (export.qr.len() + 1 + link_rows) as u16 + 2,
+    );
+    f.render_widget(Clear, dialog_area);
+
+    let block = Block::default()
+        .title(format!(
+            " Export {}/{}: {name} ",
+            export.current + 1,
+            export.links.len()
+        ))
+        .title_bottom(" ←→:Switch  w:Write subscription file  Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let inner_area = block.inner(dialog_area);
+    f.render_widget(block, dialog_area);
+
+    // Dark modules on a light background so phones can scan it in any theme
+    let qr_style = Style::default().fg(Color::Black).bg(Color::White);
+    let mut lines: Vec<Line> = export
+        .qr
+        .iter()
+        .map(|row| Line::from(Span::styled(row.clone(), qr_style)).centered())
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        link.clone(),
+        Style::default().fg(Color::White),
+    )));
+    let paragraph = Paragraph::new(lines).wrap(ratatui::widgets::Wrap { trim: false });
+    f.render_widget(paragraph, inner_area);
+}
+
+/// Render the history popup, newest applied config first
+fn render_history(f: &mut Frame, app: &App) {
+    let entries = &app.history.entries;
+    let dialog_area = dialog_rect(f, 80, entries.len() as u16 + 2);
+    f.render_widget(Clear, dialog_area);
+
+    let items: Vec<ListItem> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let node = &entry.node;
+            let marker = if i == 0 { "● " } else { "  " };
+            ListItem::new(Line::from(vec![
+                Span::styled(marker, Style::default().fg(Color::Green)),
+                Span::styled(
+                    format!("{}  ", format_time(entry.applied_at)),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(format!("{}  ", pad_string(&node.display_name(), 30))),
+                Span::styled(
+                    format!(
+                        "{:<6} {}:{}",
+                        node.protocol().as_str(),
+                        node.address,
+                        node.port
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(" History ")
+        .title_bottom(" ↑↓:Select  Enter:Revert  Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::DarkGray));
+    let mut state = ListState::default();
+    state.select(Some(app.history_selected));
+    f.render_stateful_widget(list, dialog_area, &mut state);
+}
+
+/// Render the scrollable parse report popup, one section per subscription
+fn render_report(f: &mut Frame, reports: &[(String, ParseReport)], scroll: u16) {
+    let dialog_area = dialog_rect(f, 90, 20);
+    f.render_widget(Clear, dialog_area);
+
+    let report_block = Block::default()
+        .title(" Parse Report (↑↓/PgUp/PgDn to scroll, Esc to close) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (name, report) in reports {
+        lines.push(Line::from(Span::styled(
+            format!("{name} - {}", report.summary()),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        )));
+        if report.issues.is_empty() {
+            lines.push(Line::from("      No problems found"));
+        }
+        for issue in &report.issues {
+            let kind_style = match issue.kind {
+                IssueKind::UnsupportedScheme => Style::default().fg(Color::DarkGray),
+                IssueKind::InvalidLink | IssueKind::InvalidEntry => Style::default().fg(Color::Red),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{:>5}  ", issue.line),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(format!("{:<14}", issue.kind.label()), kind_style),
+                Span::raw(format!("{}  ", issue.prefix)),
+                Span::styled(issue.message.clone(), Style::default().fg(Color::White)),
+            ]));
+        }
+    }
+
+    let issues = Paragraph::new(lines)
+        .block(report_block)
+        .scroll((scroll, 0));
+    f.render_widget(issues, dialog_area);
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::node::Node;
+
+/// Differences between cached and freshly fetched nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+impl DiffSummary {
+    /// Add the counts of another summary
+    pub fn add(&mut self, other: DiffSummary) {
+        self.added += other.added;
+        self.removed += other.removed;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+    }
+
+    /// Short description such as "+2 added, -1 removed, ~3 changed"
+    pub fn summary(&self) -> String {
+        format!(
+            "+{} added, -{} removed, ~{} changed",
+            self.added, self.removed, self.changed
+        )
+    }
+}
+
+/// Merge fresh subscription nodes with cached ones, in fresh order.
+///
+/// Nodes are matched by fingerprint first and keep their latency results;
+/// a match whose name or other settings differ counts as changed. Leftover
+/// nodes with the same name but a different server also count as changed,
+/// without latency since they point somewhere else.
+pub fn merge_nodes(cached: Vec<Node>, fresh: Vec<Node>) -> (Vec<Node>, DiffSummary) {
+    let mut diff = DiffSummary::default();
+    let mut by_fingerprint: HashMap<String, VecDeque<usize>> = HashMap::new();
+    for (i, node) in cached.iter().enumerate() {
+        by_fingerprint
+            .entry(node.fingerprint())
+            .or_default()
+            .push_back(i);
+    }
+
+    let mut used = vec![false; cached.len()];
+    let mut unmatched = Vec::new();
+    let mut merged = Vec::with_capacity(fresh.len());
+
+    for mut node in fresh {
+        let matched = by_fingerprint
+            .get_mut(&node.fingerprint())
+            .and_then(VecDeque::pop_front);
+        match matched {
+            Some(i) => {
+                used[i] = true;
+                let old = &cached[i];
+                if node.same_config(old) {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed += 1;
+                }
+                node.http_latency = old.http_latency;
+                node.tcp_latency = old.tcp_latency;
+            }
+            None => unmatched.push(merged.len()),
+        }
+        merged.push(node);
+    }
+
+    for index in unmatched {
+        let renamed = cached
+            .iter()
+            .enumerate()
+            .position(|(i, old)| !used[i] && old.name == merged[index].name);
+        match renamed {
+            Some(i) => {
+                used[i] = true;
+                diff.changed += 1;
+            }
+            None => diff.added += 1,
+        }
+    }
+    diff.removed = used.iter().filter(|u| !**u).count();
+
+    (merged, diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::LatencyStatus;
+
+    fn node(link: &str) -> Node {
+        Node::from_link(link).unwrap()
+    }
+
+    #[test]
+    fn test_merge_nodes() {
+        let mut kept = node("trojan://p@a.test.com:443#A");
+        kept.tcp_latency = LatencyStatus::Success(42);
+        let mut renamed = node("trojan://p@b.test.com:443#B");
+        renamed.http_latency = LatencyStatus::TimedOut;
+        let moved = node("trojan://p@c.test.com:443#C");
+        let gone = node("trojan://p@d.test.com:443#D");
+
+        let fresh = vec![
+            node("trojan://p@a.test.com:443#A"),
+            node("trojan://p@b.test.com:443#B%20renamed"),
+            node("trojan://p@c2.test.com:443#C"),
+            node("trojan://p@e.test.com:443#E"),
+        ];
+
+        let (merged, diff) = merge_nodes(vec![kept, renamed, moved, gone], fresh);
+        assert_eq!(
+            diff,
+            DiffSummary {
+                added: 1,
+                removed: 1,
+                changed: 2,
+                unchanged: 1,
+            }
+        );
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[0].tcp_latency, LatencyStatus::Success(42));
+        assert_eq!(merged[1].name, "B renamed");
+        assert_eq!(merged[1].http_latency, LatencyStatus::TimedOut);
+        assert_eq!(merged[2].tcp_latency, LatencyStatus::NotTested);
+        assert_eq!(diff.summary(), "+1 added, -1 removed, ~2 changed");
+    }
+}
@@ -0,0 +1,424 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::latency::TestType;
+use crate::vmess::VmessNode;
+use crate::{shadowsocks, trojan, vless};
+
+/// Latency test result status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatencyStatus {
+    /// Not tested yet
+    #[default]
+    NotTested,
+    /// Test timed out
+    TimedOut,
+    /// Test succeeded with latency in ms
+    Success(u64),
+}
+
+impl LatencyStatus {
+    pub fn is_tested(&self) -> bool {
+        !matches!(self, LatencyStatus::NotTested)
+    }
+}
+
+/// Decode base64 in any of the standard/URL-safe, padded/unpadded variants
+pub fn decode_base64(encoded: &str) -> Result<Vec<u8>> {
+    let encoded = encoded.trim();
+    general_purpose::STANDARD
+        .decode(encoded)
+        .or_else(|_| general_purpose::URL_SAFE.decode(encoded))
+        .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(encoded))
+        .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(encoded))
+        .map_err(|e| anyhow!("Base64 decode error: {e}"))
+}
+
+/// Components of a `scheme://user@host:port?params#name` share link
+pub struct ShareUrl {
+    pub user: String,
+    pub address: String,
+    pub port: u16,
+    pub name: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ShareUrl {
+    /// Parse a share link with the given scheme (e.g. "vless")
+    pub fn parse(link: &str, scheme: &str) -> Result<Self> {
+        let link = link.trim();
+        if !link.starts_with(&format!("{scheme}://")) {
+            return Err(anyhow!(
+                "Invalid {scheme} link: must start with {scheme}://"
+            ));
+        }
+
+        let url = Url::parse(link).map_err(|e| anyhow!("URL parse error: {e}"))?;
+
+        let user = percent_decode_str(url.username())
+            .decode_utf8_lossy()
+            .to_string();
+        if user.is_empty() {
+            return Err(anyhow!("Missing user info in {scheme} link"));
+        }
+
+        let address = url
+            .host_str()
+            .ok_or_else(|| anyhow!("Missing host in {scheme} link"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = url
+            .port()
+            .ok_or_else(|| anyhow!("Missing port in {scheme} link"))?;
+
+        let name = url
+            .fragment()
+            .map(|f| percent_decode_str(f).decode_utf8_lossy().to_string())
+            .unwrap_or_default();
+
+        let params = url
+            .query_pairs()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        Ok(ShareUrl {
+            user,
+            address,
+            port,
+            name,
+            params,
+        })
+    }
+
+    /// Get the value of a query parameter
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Build a `scheme://user@host:port?params#name` share link
+    pub fn to_link(&self, scheme: &str) -> String {
+        let host = if self.address.contains(':') {
+            format!("[{}]", self.address)
+        } else {
+            self.address.clone()
+        };
+        let mut link = format!(
+            "{scheme}://{}@{host}:{}",
+            utf8_percent_encode(&self.user, NON_ALPHANUMERIC),
+            self.port
+        );
+        if !self.params.is_empty() {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(&self.params)
+                .finish();
+            link.push('?');
+            link.push_str(&query);
+        }
+        if !self.name.is_empty() {
+            link.push('#');
+            link.push_str(&utf8_percent_encode(&self.name, NON_ALPHANUMERIC).to_string());
+        }
+        link
+    }
+
+    /// Extract the standard transport parameters (type, headerType, host, path, serviceName)
+    pub fn transport(&self) -> Transport {
+        let get = |key| self.param(key).unwrap_or_default().to_string();
+        Transport {
+            network: get("type"),
+            header_type: get("headerType"),
+            host: get("host"),
+            path: get("path"),
+            service_name: get("serviceName"),
+        }
+    }
+}
+
+/// Supported proxy protocols
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Vmess,
+    Vless,
+    Trojan,
+    Shadowsocks,
+}
+
+impl Protocol {
+    /// Protocol name as used by xray outbounds
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Vmess => "vmess",
+            Protocol::Vless => "vless",
+            Protocol::Trojan => "trojan",
+            Protocol::Shadowsocks => "shadowsocks",
+        }
+    }
+
+    /// Short name for display
+    pub fn label(self) -> &'static str {
+        match self {
+            Protocol::Shadowsocks => "ss",
+            other => other.as_str(),
+        }
+    }
+}
+
+/// Transport layer settings shared by all stream-based protocols
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transport {
+    /// Network: tcp, ws, grpc, h2, httpupgrade (empty means tcp)
+    pub network: String,
+    /// Header obfuscation type (e.g. http for tcp)
+    pub header_type: String,
+    pub host: String,
+    pub path: String,
+    pub service_name: String,
+}
+
+impl Transport {
+    /// Standard share link parameters of the transport, omitting empty values
+    pub fn params(&self) -> Vec<(String, String)> {
+        [
+            ("type", &self.network),
+            ("headerType", &self.header_type),
+            ("host", &self.host),
+            ("path", &self.path),
+            ("serviceName", &self.service_name),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+    }
+}
+
+/// TLS parameters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    pub sni: String,
+    pub alpn: String,
+    pub fingerprint: String,
+    pub allow_insecure: bool,
+}
+
+impl TlsSettings {
+    /// Share link parameters (sni, fp, alpn, allowInsecure), omitting empty values
+    pub fn params(&self) -> Vec<(String, String)> {
+        let mut params: Vec<(String, String)> = [
+            ("sni", &self.sni),
+            ("fp", &self.fingerprint),
+            ("alpn", &self.alpn),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+        if self.allow_insecure {
+            params.push(("allowInsecure".to_string(), "1".to_string()));
+        }
+        params
+    }
+}
+
+/// REALITY parameters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RealitySettings {
+    pub sni: String,
+    pub fingerprint: String,
+    pub public_key: String,
+    pub short_id: String,
+    pub spider_x: String,
+}
+
+/// Stream security layer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Security {
+    #[default]
+    None,
+    Tls(TlsSettings),
+    Reality(RealitySettings),
+}
+
+/// Protocol-specific payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "protocol", rename_all = "lowercase")]
+pub enum ProtocolSettings {
+    Vmess {
+        id: String,
+        #[serde(default)]
+        alter_id: u32,
+    },
+    Vless {
+        id: String,
+        #[serde(default)]
+        flow: String,
+        #[serde(default)]
+        encryption: String,
+    },
+    Trojan {
+        password: String,
+    },
+    Shadowsocks {
+        method: String,
+        password: String,
+        /// SIP003 plugin name (e.g. obfs-local, v2ray-plugin)
+        #[serde(default)]
+        plugin: String,
+        /// SIP003 plugin options, semicolon separated
+        #[serde(default)]
+        plugin_opts: String,
+    },
+}
+
+/// A subscription node: a protocol-agnostic header plus protocol settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(default)]
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    #[serde(default)]
+    pub transport: Transport,
+    #[serde(default)]
+    pub security: Security,
+    #[serde(flatten)]
+    pub settings: ProtocolSettings,
+    // Runtime fields for latency
+    #[serde(skip)]
+    pub http_latency: LatencyStatus,
+    #[serde(skip)]
+    pub tcp_latency: LatencyStatus,
+}
+
+impl Node {
+    /// Create a node with default transport and no stream security
+    pub fn new(name: String, address: String, port: u16, settings: ProtocolSettings) -> Self {
+        Node {
+            name,
+            address,
+            port,
+            transport: Transport::default(),
+            security: Security::None,
+            settings,
+            http_latency: LatencyStatus::NotTested,
+            tcp_latency: LatencyStatus::NotTested,
+        }
+    }
+
+    /// Parse a share link of any supported protocol
+    pub fn from_link(link: &str) -> Result<Self> {
+        let link = link.trim();
+        if link.starts_with("vmess://") {
+            VmessNode::from_link(link).map(Node::from)
+        } else if link.starts_with("vless://") {
+            vless::from_link(link)
+        } else if link.starts_with("trojan://") {
+            trojan::from_link(link)
+        } else if link.starts_with("ss://") {
+            shadowsocks::from_link(link)
+        } else {
+            Err(anyhow!("Unsupported link scheme"))
+        }
+    }
+
+    /// Encode as a share link of the node's protocol
+    pub fn to_link(&self) -> String {
+        match self.settings {
+            ProtocolSettings::Vmess { .. } => VmessNode::from(self).to_link(),
+            ProtocolSettings::Vless { .. } => vless::to_link(self),
+            ProtocolSettings::Trojan { .. } => trojan::to_link(self),
+            ProtocolSettings::Shadowsocks { .. } => shadowsocks::to_link(self),
+        }
+    }
+
+    /// Check whether a link uses a scheme this crate can parse
+    pub fn is_supported_link(link: &str) -> bool {
+        link.starts_with("vmess://")
+            || link.starts_with("vless://")
+            || link.starts_with("trojan://")
+            || link.starts_with("ss://")
+    }
+
+    /// Protocol of this node
+    pub fn protocol(&self) -> Protocol {
+        match self.settings {
+            ProtocolSettings::Vmess { .. } => Protocol::Vmess,
+            ProtocolSettings::Vless { .. } => Protocol::Vless,
+            ProtocolSettings::Trojan { .. } => Protocol::Trojan,
+            ProtocolSettings::Shadowsocks { .. } => Protocol::Shadowsocks,
+        }
+    }
+
+    /// Get display name (name or address:port if name is empty)
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            format!("{}:{}", self.address, self.port)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// User id or password used to authenticate with the server
+    pub fn credential(&self) -> &str {
+        match &self.settings {
+            ProtocolSettings::Vmess { id, .. } | ProtocolSettings::Vless { id, .. } => id,
+            ProtocolSettings::Trojan { password }
+            | ProtocolSettings::Shadowsocks { password, .. } => password,
+        }
+    }
+
+    /// Stable identity of the server behind this node: protocol, address, port,
+    /// credential and transport. The display name is deliberately left out.
+    pub fn fingerprint(&self) -> String {
+        let t = &self.transport;
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.protocol().as_str(),
+            self.address.to_lowercase(),
+            self.port,
+            self.credential(),
+            t.network,
+            t.header_type,
+            t.host,
+            t.path,
+            t.service_name
+        )
+    }
+
+    /// Compare everything except runtime latency fields
+    pub fn same_config(&self, other: &Node) -> bool {
+        self.name == other.name
+            && self.address == other.address
+            && self.port == other.port
+            && self.transport == other.transport
+            && self.security == other.security
+            && self.settings == other.settings
+    }
+
+    /// Get latency for the given test type
+    pub fn latency(&self, test_type: TestType) -> LatencyStatus {
+        match test_type {
+            TestType::Http => self.http_latency,
+            TestType::Tcp => self.tcp_latency,
+        }
+    }
+
+    /// Set latency for the given test type
+    pub fn set_latency(&mut self, test_type: TestType, latency: LatencyStatus) {
+        match test_type {
+            TestType::Http => self.http_latency = latency,
+            TestType::Tcp => self.tcp_latency = latency,
+        }
+    }
+}
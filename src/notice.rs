@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::node::Node;
+
+/// Keywords of informational entries providers commonly disguise as nodes
+pub const DEFAULT_INFO_KEYWORDS: &[&str] = &[
+    "剩余流量",
+    "套餐到期",
+    "到期时间",
+    "过期时间",
+    "距离下次重置",
+    "官网",
+    "网址",
+    "traffic",
+    "expire",
+    "website",
+];
+
+/// A rule matching node names that carry subscription information instead of a server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InfoRule {
+    /// Case-insensitive substring
+    Keyword(String),
+    /// Regular expression
+    Regex(String),
+}
+
+/// Compiled info rules
+#[derive(Debug, Clone, Default)]
+pub struct InfoRules {
+    keywords: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl InfoRules {
+    /// Compile rules, failing on the first invalid regular expression
+    pub fn compile(rules: &[InfoRule]) -> Result<Self> {
+        let mut compiled = InfoRules::default();
+        for rule in rules {
+            match rule {
+                InfoRule::Keyword(keyword) => compiled.keywords.push(keyword.to_lowercase()),
+                InfoRule::Regex(pattern) => compiled.patterns.push(
+                    Regex::new(pattern)
+                        .map_err(|e| anyhow!("Invalid info rule regex {pattern:?}: {e}"))?,
+                ),
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// Rules built from `DEFAULT_INFO_KEYWORDS`
+    pub fn defaults() -> Self {
+        InfoRules {
+            keywords: DEFAULT_INFO_KEYWORDS
+                .iter()
+                .map(|k| k.to_string())
+                .collect(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Check whether a node name is informational
+    pub fn matches(&self, name: &str) -> bool {
+        let lower = name.to_lowercase();
+        self.keywords.iter().any(|k| lower.contains(k.as_str()))
+            || self.patterns.iter().any(|p| p.is_match(name))
+    }
+
+    /// Remove informational entries from `nodes`, returning their names
+    pub fn extract(&self, nodes: &mut Vec<Node>) -> Vec<String> {
+        let mut notices = Vec::new();
+        nodes.retain(|node| {
+            if self.matches(&node.name) {
+                notices.push(node.name.clone());
+                false
+            } else {
+                true
+            }
+        });
+        notices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::ProtocolSettings;
+
+    fn node(name: &str) -> Node {
+        Node::new(
+            name.to_string(),
+            "1.2.3.4".to_string(),
+            443,
+            ProtocolSettings::Trojan {
+                password: "p".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_extract_info_nodes() {
+        let mut nodes = vec![
+            node("剩余流量：50GB"),
+            node("HK 01"),
+            node("套餐到期：2026-12-01"),
+            node("Plan: Pro"),
+        ];
+
+        let notices = InfoRules::defaults().extract(&mut nodes);
+        assert_eq!(notices, vec!["剩余流量：50GB", "套餐到期：2026-12-01"]);
+        assert_eq!(nodes.len(), 2);
+
+        let rules = InfoRules::compile(&[
+            InfoRule::Keyword("PLAN".to_string()),
+            InfoRule::Regex(r"^\d+ GB$".to_string()),
+        ])
+        .unwrap();
+        assert!(rules.matches("Plan: Pro"));
+        assert!(rules.matches("50 GB"));
+        assert!(!rules.matches("HK 01"));
+
+        assert!(InfoRules::compile(&[InfoRule::Regex("(".to_string())]).is_err());
+    }
+}
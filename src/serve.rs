@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::clash::clash_profile;
+use crate::export::subscription_body;
+use crate::node::{LatencyStatus, Node};
+use crate::singbox::singbox_config;
+
+/// Node list shared between the TUI and the subscription server
+pub type SharedNodes = Arc<RwLock<Vec<Node>>>;
+
+/// Largest request head accepted, in bytes
+const MAX_REQUEST_SIZE: usize = 8192;
+/// Time a client gets to send its request and read the response
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after a failed accept, e.g. when out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Latency used to order served nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServeSort {
+    #[default]
+    Tcp,
+    Http,
+    /// Keep the node list order
+    None,
+}
+
+/// Rewrite node names matching a regular expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameRule {
+    pub pattern: String,
+    /// Replacement, may refer to capture groups as `$1`
+    pub replace: String,
+}
+
+/// Settings of the embedded subscription server
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServeOptions {
+    /// Address to listen on, e.g. "127.0.0.1:8964"
+    #[serde(default)]
+    pub listen: String,
+    /// Required as `Authorization: Bearer <token>` or `?token=<token>` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Serve only the N fastest nodes by default, overridable with `?top=N`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<usize>,
+    /// Serve only nodes whose name matches this regular expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+    /// Leave out nodes whose name matches this regular expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    /// Name rewrites applied in order after filtering
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rename: Vec<RenameRule>,
+    #[serde(default)]
+    pub sort: ServeSort,
+}
+
+/// Compiled filter, rename and sort settings
+#[derive(Debug, Clone)]
+pub struct NodeFilter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    rename: Vec<(Regex, String)>,
+    sort: ServeSort,
+    top: Option<usize>,
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| anyhow!("Invalid serve regex {pattern:?}: {e}"))
+}
+
+impl NodeFilter {
+    /// Compile the regular expressions of the server settings
+    pub fn compile(options: &ServeOptions) -> Result<Self> {
+        Ok(NodeFilter {
+            include: options.include.as_deref().map(compile_regex).transpose()?,
+            exclude: options.exclude.as_deref().map(compile_regex).transpose()?,
+            rename: options
+                .rename
+                .iter()
+                .map(|rule| Ok((compile_regex(&rule.pattern)?, rule.replace.clone())))
+                .collect::<Result<_>>()?,
+            sort: options.sort,
+            top: options.top,
+        })
+    }
+
+    /// Filter, rename and sort nodes, keeping at most `top` (or the configured
+    /// default). Nodes that timed out are left out when sorting by latency.
+    pub fn apply(&self, nodes: &[Node], top: Option<usize>) -> Vec<Node> {
+        let mut selected: Vec<Node> = nodes
+            .iter()
+            .filter(|n| self.include.as_ref().is_none_or(|re| re.is_match(&n.name)))
+            .filter(|n| !self.exclude.as_ref().is_some_and(|re| re.is_match(&n.name)))
+            .cloned()
+            .collect();
+
+        let latency = |node: &Node| match self.sort {
+            ServeSort::Tcp => Some(node.tcp_latency),
+            ServeSort::Http => Some(node.http_latency),
+            ServeSort::None => None,
+        };
+        if self.sort != ServeSort::None {
+            selected.retain(|n| latency(n) != Some(LatencyStatus::TimedOut));
+            // Untested nodes go last, in list order
+            selected.sort_by_key(|n| match latency(n) {
+                Some(LatencyStatus::Success(ms)) => (0, ms),
+                _ => (1, 0),
+            });
+        }
+        if let Some(top) = top.or(self.top) {
+            selected.truncate(top);
+        }
+
+        for node in &mut selected {
+            for (pattern, replace) in &self.rename {
+                node.name = pattern
+                    .replace_all(&node.name, replace.as_str())
+                    .into_owned();
+            }
+        }
+        selected
+    }
+}
+
+/// An HTTP response
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{status}\n"),
+        }
+    }
+}
+
+/// Bind the listen address of the server
+pub async fn bind(options: &ServeOptions) -> Result<TcpListener> {
+    TcpListener::bind(&options.listen)
+        .await
+        .map_err(|e| anyhow!("Failed to listen on {}: {e}", options.listen))
+}
+
+/// Serve `/sub` (base64 share links), `/clash` (Clash YAML) and `/singbox`
+/// (sing-box JSON) until the task is dropped
+pub async fn serve(
+    listener: TcpListener,
+    nodes: SharedNodes,
+    filter: NodeFilter,
+    token: Option<String>,
+) {
+    let filter = Arc::new(filter);
+    let token = Arc::new(token);
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            tokio::time::sleep(ACCEPT_BACKOFF).await;
+            continue;
+        };
+        let nodes = nodes.clone();
+        let filter = filter.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            let handled = handle_connection(stream, &nodes, &filter, token.as_deref());
+            let _ = tokio::time::timeout(CONNECTION_TIMEOUT, handled).await;
+        });
+    }
+}
+
+/// Answer one request and close the connection
+async fn handle_connection(
+    mut stream: TcpStream,
+    nodes: &SharedNodes,
+    filter: &NodeFilter,
+    token: Option<&str>,
+) -> Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || buffer.len() + n > MAX_REQUEST_SIZE {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&buffer);
+    let response = respond(&request, nodes, filter, token);
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        if response.status.starts_with("401") {
+            "WWW-Authenticate: Bearer\r\n"
+        } else {
+            ""
+        }
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Compare secrets without stopping at the first differing byte
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Route a raw request head to a response
+fn respond(
+    request: &str,
+    nodes: &SharedNodes,
+    filter: &NodeFilter,
+    token: Option<&str>,
+) -> Response {
+    let mut lines = request.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Response::error("400 Bad Request");
+    };
+    if method != "GET" {
+        return Response::error("405 Method Not Allowed");
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+
+    if let Some(token) = token {
+        let bearer = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+            .and_then(|(_, value)| value.trim().strip_prefix("Bearer "))
+            .map(str::trim);
+        let matches = |given: Option<&str>| given.is_some_and(|g| constant_time_eq(g, token));
+        if !matches(bearer) && !matches(param("token")) {
+            return Response::error("401 Unauthorized");
+        }
+    }
+
+    let top = match param("top").map(str::parse::<usize>) {
+        Some(Ok(top)) => Some(top),
+        Some(Err(_)) => return Response::error("400 Bad Request"),
+        None => None,
+    };
+    let selected = {
+        let nodes = nodes.read().unwrap_or_else(|e| e.into_inner());
+        filter.apply(&nodes, top)
+    };
+
+    match path {
+        "/sub" => {
+            let links: Vec<String> = selected.iter().map(Node::to_link).collect();
+            Response::ok("text/plain; charset=utf-8", subscription_body(&links))
+        }
+        "/clash" => match clash_profile(&selected) {
+            Ok(yaml) => Response::ok("text/yaml; charset=utf-8", yaml),
+            Err(_) => Response::error("500 Internal Server Error"),
+        },
+        "/singbox" => Response::ok(
+            "application/json",
+            serde_json::to_string_pretty(&singbox_config(&selected)).unwrap_or_default(),
+        ),
+        _ => Response::error("404 Not Found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::decode_base64;
+    use crate::subscribe::parse_subscription_content;
+
+    fn node(link: &str, tcp: LatencyStatus) -> Node {
+        let mut node = Node::from_link(link).unwrap();
+        node.tcp_latency = tcp;
+        node
+    }
+
+    #[tokio::test]
+    async fn test_serve_localhost() {
+        let nodes = vec![
+            node(
+                "trojan://p@a.test.com:443#HK%2001",
+                LatencyStatus::Success(80),
+            ),
+            node(
+                "trojan://p@b.test.com:443#HK%2002",
+                LatencyStatus::Success(20),
+            ),
+            node("trojan://p@c.test.com:443#HK%2003", LatencyStatus::TimedOut),
+            node(
+                "trojan://p@d.test.com:443#US%2001",
+                LatencyStatus::Success(10),
+            ),
+        ];
+        let options = ServeOptions {
+            listen: "127.0.0.1:0".to_string(),
+            token: Some("secret".to_string()),
+            include: Some("^HK".to_string()),
+            rename: vec![RenameRule {
+                pattern: "^HK".to_string(),
+                replace: "Hong Kong".to_string(),
+            }],
+            ..Default::default()
+        };
+        let listener = bind(&options).await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let filter = NodeFilter::compile(&options).unwrap();
+        tokio::spawn(serve(
+            listener,
+            Arc::new(RwLock::new(nodes)),
+            filter,
+            options.token.clone(),
+        ));
+
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let get = |path: &str| {
+            client
+                .get(format!("{base}{path}"))
+                .bearer_auth("secret")
+                .send()
+        };
+
+        let unauthorized = client.get(format!("{base}/sub")).send().await.unwrap();
+        assert_eq!(unauthorized.status(), 401);
+        let wrong = client
+            .get(format!("{base}/sub?token=secreT"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(wrong.status(), 401);
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secrets"));
+
+        let body = get("/sub").await.unwrap().text().await.unwrap();
+        let links = String::from_utf8(decode_base64(&body).unwrap()).unwrap();
+        let names: Vec<String> = links
+            .lines()
+            .map(|l| Node::from_link(l).unwrap().name)
+            .collect();
+        assert_eq!(names, vec!["Hong Kong 02", "Hong Kong 01"]);
+
+        let clash = get("/clash?top=1").await.unwrap().text().await.unwrap();
+        let (served, _) = parse_subscription_content(&clash, &Default::default()).unwrap();
+        assert_eq!(served.len(), 1);
+        assert_eq!(served[0].name, "Hong Kong 02");
+
+        let singbox = client
+            .get(format!("{base}/singbox?token=secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(singbox.status(), 200);
+        let (served, _) =
+            parse_subscription_content(&singbox.text().await.unwrap(), &Default::default())
+                .unwrap();
+        assert_eq!(served.len(), 2);
+
+        assert_eq!(get("/missing").await.unwrap().status(), 404);
+    }
+}
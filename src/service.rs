@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// Default name of the xray service unit
+pub const DEFAULT_SERVICE_NAME: &str = "xray";
+/// Default signal sent to the process of a PID file
+pub const DEFAULT_SIGNAL: &str = "HUP";
+
+/// Number of 500ms polls in a success check (3 seconds)
+const CHECK_ATTEMPTS: usize = 6;
+
+/// Restarts or reloads xray after its config changed
+pub trait ServiceController {
+    /// Restart or reload the service and verify it is running again. Returns
+    /// a short description of what happened.
+    fn restart(&self) -> Result<String>;
+}
+
+fn default_service_name() -> String {
+    DEFAULT_SERVICE_NAME.to_string()
+}
+
+fn default_signal() -> String {
+    DEFAULT_SIGNAL.to_string()
+}
+
+/// How xray is managed on this machine
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ServiceBackend {
+    /// Find xray with `pgrep`, send SIGHUP and wait for the PID to change
+    #[default]
+    Pgrep,
+    /// `systemctl restart <unit>` (or `reload`), then `systemctl is-active`
+    Systemd {
+        #[serde(default = "default_service_name")]
+        unit: String,
+        #[serde(default)]
+        reload: bool,
+    },
+    /// OpenRC `rc-service <service> restart` (or `reload`), then `status`
+    Openrc {
+        #[serde(default = "default_service_name")]
+        service: String,
+        #[serde(default)]
+        reload: bool,
+    },
+    /// Send a signal to the PID in a file, then wait for the file to name a new
+    /// live process (or the old one to exit and come back)
+    Pidfile {
+        path: String,
+        #[serde(default = "default_signal")]
+        signal: String,
+    },
+    /// Run a shell command; an optional check command must succeed afterwards
+    Command {
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        check: Option<String>,
+    },
+}
+
+impl FromStr for ServiceBackend {
+    type Err = anyhow::Error;
+
+    /// Parse `pgrep`, `systemd[:unit]`, `systemd-reload[:unit]`,
+    /// `openrc[:service]`, `openrc-reload[:service]`, `pidfile:<path>` or
+    /// `command:<shell command>`
+    fn from_str(spec: &str) -> Result<Self> {
+        let (kind, arg) = match spec.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg.trim()).filter(|a| !a.is_empty())),
+            None => (spec, None),
+        };
+        let kind = kind.trim();
+        let name = || arg.map_or_else(default_service_name, String::from);
+        let required = |what: &str| {
+            arg.map(String::from)
+                .ok_or_else(|| anyhow!("Service \"{kind}\" needs a {what}, e.g. {kind}:<{what}>"))
+        };
+        Ok(match kind {
+            "pgrep" => ServiceBackend::Pgrep,
+            "systemd" | "systemd-reload" => ServiceBackend::Systemd {
+                unit: name(),
+                reload: kind.ends_with("-reload"),
+            },
+            "openrc" | "openrc-reload" => ServiceBackend::Openrc {
+                service: name(),
+                reload: kind.ends_with("-reload"),
+            },
+            "pidfile" => ServiceBackend::Pidfile {
+                path: required("path")?,
+                signal: default_signal(),
+            },
+            "command" => ServiceBackend::Command {
+                command: required("command")?,
+                check: None,
+            },
+            other => return Err(anyhow!("Unknown service backend \"{other}\"")),
+        })
+    }
+}
+
+impl ServiceController for ServiceBackend {
+    fn restart(&self) -> Result<String> {
+        match self {
+            ServiceBackend::Pgrep => restart_by_pgrep(),
+            ServiceBackend::Systemd { unit, reload } => {
+                let action = if *reload { "reload" } else { "restart" };
+                run("systemctl", &[action, unit])?;
+                let active =
+                    wait_until(|| run("systemctl", &["is-active", "--quiet", unit]).is_ok());
+                if !active {
+                    return Err(anyhow!("{unit} is not active after systemctl {action}"));
+                }
+                Ok(format!("systemctl {action} {unit}: active"))
+            }
+            ServiceBackend::Openrc { service, reload } => {
+                let action = if *reload { "reload" } else { "restart" };
+                run("rc-service", &[service, action])?;
+                let started = wait_until(|| run("rc-service", &[service, "status"]).is_ok());
+                if !started {
+                    return Err(anyhow!(
+                        "{service} is not started after rc-service {action}"
+                    ));
+                }
+                Ok(format!("rc-service {service} {action}: started"))
+            }
+            ServiceBackend::Pidfile { path, signal } => {
+                let pid = read_pid_file(path)?;
+                run("kill", &[&format!("-{signal}"), &pid.to_string()])?;
+                // Success is a new PID in the file, or the old one seen gone and back
+                let mut exited = false;
+                let mut current = None;
+                let restarted = wait_until(|| {
+                    current = read_pid_file(path).ok().filter(|p| is_alive(*p));
+                    exited |= current.is_none();
+                    current.is_some_and(|new_pid| new_pid != pid || exited)
+                });
+                match current {
+                    Some(new_pid) if restarted => {
+                        Ok(format!("SIG{signal} sent (PID: {pid} -> {new_pid})"))
+                    }
+                    Some(_) => Err(anyhow!(
+                        "PID {pid} of {path} unchanged after SIG{signal} - restart failed"
+                    )),
+                    None => Err(anyhow!(
+                        "Process of {path} is not running after SIG{signal} to PID {pid}"
+                    )),
+                }
+            }
+            ServiceBackend::Command { command, check } => {
+                run("sh", &["-c", command])?;
+                let Some(check) = check else {
+                    return Ok(format!("`{command}` succeeded"));
+                };
+                if !wait_until(|| run("sh", &["-c", check]).is_ok()) {
+                    return Err(anyhow!("Check `{check}` failed after `{command}`"));
+                }
+                Ok(format!("`{command}` succeeded, `{check}` passed"))
+            }
+        }
+    }
+}
+
+/// Run a program, failing with the last line of its output when it exits non-zero
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("Failed to run {program}: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reason = stderr
+        .lines()
+        .chain(stdout.lines())
+        .rfind(|line| !line.trim().is_empty())
+        .map_or_else(|| output.status.to_string(), |line| line.trim().to_string());
+    Err(anyhow!("{program} {} failed: {reason}", args.join(" ")))
+}
+
+/// Poll every 500ms until `check` passes, for up to 3 seconds
+fn wait_until(mut check: impl FnMut() -> bool) -> bool {
+    for _ in 0..CHECK_ATTEMPTS {
+        thread::sleep(Duration::from_millis(500));
+        if check() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Read the PID written in a PID file
+fn read_pid_file(path: &str) -> Result<u32> {
+    let content = fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {path}: {e}"))?;
+    content
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("{path} does not contain a PID"))
+}
+
+/// Whether a process with the PID exists
+fn is_alive(pid: u32) -> bool {
+    run("kill", &["-0", &pid.to_string()]).is_ok()
+}
+
+/// Get xray process ID
+fn get_xray_pid() -> Option<u32> {
+    let output = Command::new("pgrep").arg("xray").output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Get the first PID if there are multiple
+    stdout.lines().next()?.trim().parse().ok()
+}
+
+/// Restart xray service by sending SIGHUP signal
+fn restart_by_pgrep() -> Result<String> {
+    // Get current PID
+    let old_pid = get_xray_pid().ok_or_else(|| anyhow!("xray process not found"))?;
+
+    // Send SIGHUP to reload config
+    let output = Command::new("kill")
+        .args(["-HUP", &old_pid.to_string()])
+        .output()
+        .map_err(|e| anyhow!("Failed to send HUP signal: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "Failed to send HUP to xray (PID {old_pid}): {stderr}"
+        ));
+    }
+
+    // Poll every 500ms for up to 3 seconds to check if PID changed (restart success)
+    for attempt in 0..CHECK_ATTEMPTS {
+        thread::sleep(Duration::from_millis(500));
+
+        match get_xray_pid() {
+            Some(new_pid) => {
+                if new_pid != old_pid {
+                    // PID changed - restart success
+                    return Ok(format!("xray restarted (PID: {old_pid} -> {new_pid})"));
+                }
+                // PID unchanged, keep waiting unless this is the last attempt
+                if attempt == CHECK_ATTEMPTS - 1 {
+                    return Err(anyhow!(
+                        "xray PID unchanged ({old_pid}) after 3 seconds - restart failed"
+                    ));
+                }
+            }
+            None => {
+                // Process not found, wait a bit more unless this is the last attempt
+                if attempt == CHECK_ATTEMPTS - 1 {
+                    return Err(anyhow!(
+                        "xray process (PID {old_pid}) disappeared after HUP signal"
+                    ));
+                }
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Timeout waiting for xray (PID {old_pid}) to restart"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_service_backend() {
+        assert_eq!(
+            "pgrep".parse::<ServiceBackend>().unwrap(),
+            ServiceBackend::Pgrep
+        );
+        assert_eq!(
+            "systemd".parse::<ServiceBackend>().unwrap(),
+            ServiceBackend::Systemd {
+                unit: "xray".to_string(),
+                reload: false
+            }
+        );
+        assert_eq!(
+            "openrc-reload:xray-core".parse::<ServiceBackend>().unwrap(),
+            ServiceBackend::Openrc {
+                service: "xray-core".to_string(),
+                reload: true
+            }
+        );
+        assert_eq!(
+            "command:supervisorctl restart xray"
+                .parse::<ServiceBackend>()
+                .unwrap(),
+            ServiceBackend::Command {
+                command: "supervisorctl restart xray".to_string(),
+                check: None
+            }
+        );
+        assert_eq!(
+            " systemd-reload :xray".parse::<ServiceBackend>().unwrap(),
+            ServiceBackend::Systemd {
+                unit: "xray".to_string(),
+                reload: true
+            }
+        );
+        assert!("pidfile".parse::<ServiceBackend>().is_err());
+        assert!("launchd".parse::<ServiceBackend>().is_err());
+
+        let config: ServiceBackend =
+            serde_json::from_str(r#"{"type": "pidfile", "path": "/run/xray.pid"}"#).unwrap();
+        assert_eq!(
+            config,
+            ServiceBackend::Pidfile {
+                path: "/run/xray.pid".to_string(),
+                signal: "HUP".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_command_backend_checks() {
+        let ok = ServiceBackend::Command {
+            command: "true".to_string(),
+            check: None,
+        };
+        assert!(ok.restart().is_ok());
+
+        let failing = ServiceBackend::Command {
+            command: "echo boom >&2; exit 3".to_string(),
+            check: None,
+        };
+        let err = failing.restart().unwrap_err().to_string();
+        assert!(err.contains("boom"), "{err}");
+
+        let pid_file =
+            std::env::temp_dir().join(format!("subman-service-{}.pid", std::process::id()));
+        fs::write(&pid_file, std::process::id().to_string()).unwrap();
+        let path = pid_file.to_string_lossy().into_owned();
+        assert_eq!(read_pid_file(&path).unwrap(), std::process::id());
+        assert!(is_alive(std::process::id()));
+        fs::remove_file(&pid_file).unwrap();
+    }
+
+    #[test]
+    fn test_pidfile_backend_needs_new_pid() {
+        let pid_file =
+            std::env::temp_dir().join(format!("subman-pidfile-{}.pid", std::process::id()));
+        let path = pid_file.to_string_lossy().into_owned();
+        // A supervisor that respawns its child and records the child's PID
+        let mut supervisor = Command::new("sh")
+            .args([
+                "-c",
+                &format!("while :; do sleep 30 & echo $! > {path}; wait $!; done"),
+            ])
+            .spawn()
+            .unwrap();
+        assert!(wait_until(|| read_pid_file(&path).is_ok()));
+        let first = read_pid_file(&path).unwrap();
+
+        // Still the same live process: not a restart
+        let unchanged = ServiceBackend::Pidfile {
+            path: path.clone(),
+            signal: "CONT".to_string(),
+        };
+        let err = unchanged.restart().unwrap_err().to_string();
+        assert!(err.contains("unchanged"), "{err}");
+
+        let restart = ServiceBackend::Pidfile {
+            path: path.clone(),
+            signal: "TERM".to_string(),
+        };
+        let message = restart.restart().unwrap();
+        assert!(message.contains(&format!("{first} -> ")), "{message}");
+
+        supervisor.kill().unwrap();
+        supervisor.wait().unwrap();
+        let _ = run("kill", &[&read_pid_file(&path).unwrap().to_string()]);
+        fs::remove_file(&pid_file).unwrap();
+    }
+}
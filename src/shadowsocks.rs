@@ -0,0 +1,299 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+
+use crate::node::{decode_base64, Node, ProtocolSettings};
+
+/// Ciphers supported by the xray shadowsocks outbound
+const SUPPORTED_METHODS: &[&str] = &[
+    "aes-128-gcm",
+    "aes-256-gcm",
+    "chacha20-poly1305",
+    "chacha20-ietf-poly1305",
+    "xchacha20-poly1305",
+    "xchacha20-ietf-poly1305",
+    "2022-blake3-aes-128-gcm",
+    "2022-blake3-aes-256-gcm",
+    "2022-blake3-chacha20-poly1305",
+    "none",
+    "plain",
+];
+
+/// Parse an ss:// link in SIP002 or legacy fully-base64 form into a Node
+pub fn from_link(link: &str) -> Result<Node> {
+    let link = link.trim();
+    let Some(rest) = link.strip_prefix("ss://") else {
+        return Err(anyhow!("Invalid ss link: must start with ss://"));
+    };
+
+    let (rest, name) = match rest.split_once('#') {
+        Some((rest, fragment)) => (
+            rest,
+            percent_decode_str(fragment).decode_utf8_lossy().to_string(),
+        ),
+        None => (rest, String::new()),
+    };
+
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, query),
+        None => (rest, ""),
+    };
+    let rest = rest.trim_end_matches('/');
+
+    // SIP002 keeps host:port in clear text, the legacy form encodes everything
+    let (userinfo, server) = match rest.rsplit_once('@') {
+        Some((userinfo, server)) => (decode_userinfo(userinfo)?, server.to_string()),
+        None => {
+            let decoded = decode_base64(rest)?;
+            let decoded =
+                String::from_utf8(decoded).map_err(|e| anyhow!("UTF-8 decode error: {e}"))?;
+            let (userinfo, server) = decoded
+                .rsplit_once('@')
+                .ok_or_else(|| anyhow!("Missing server in ss link"))?;
+            (userinfo.to_string(), server.to_string())
+        }
+    };
+
+    let (method, password) = userinfo
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Missing method or password in ss link"))?;
+    let method = check_method(method)?;
+
+    let (address, port) = server
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("Missing port in ss link"))?;
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let port = port
+        .parse()
+        .map_err(|_| anyhow!("Invalid port in ss link: {port}"))?;
+
+    let mut plugin = String::new();
+    let mut plugin_opts = String::new();
+    for pair in query.split('&') {
+        if let Some(value) = pair.strip_prefix("plugin=") {
+            let value = percent_decode_str(value).decode_utf8_lossy();
+            match value.split_once(';') {
+                Some((name, opts)) => {
+                    plugin = name.to_string();
+                    plugin_opts = opts.to_string();
+                }
+                None => plugin = value.to_string(),
+            }
+        }
+    }
+
+    Ok(Node::new(
+        name,
+        address.to_string(),
+        port,
+        ProtocolSettings::Shadowsocks {
+            method,
+            password: password.to_string(),
+            plugin,
+            plugin_opts,
+        },
+    ))
+}
+
+/// Encode a shadowsocks node as a SIP002 ss:// link
+pub fn to_link(node: &Node) -> String {
+    let ProtocolSettings::Shadowsocks {
+        method,
+        password,
+        plugin,
+        plugin_opts,
+    } = &node.settings
+    else {
+        return String::new();
+    };
+    // SIP002 requires plain user info for 2022 ciphers and base64 for the rest
+    let userinfo = if method.starts_with("2022-") {
+        format!(
+            "{}:{}",
+            utf8_percent_encode(method, NON_ALPHANUMERIC),
+            utf8_percent_encode(password, NON_ALPHANUMERIC)
+        )
+    } else {
+        general_purpose::URL_SAFE_NO_PAD.encode(format!("{method}:{password}"))
+    };
+    let host = if node.address.contains(':') {
+        format!("[{}]", node.address)
+    } else {
+        node.address.clone()
+    };
+
+    let mut link = format!("ss://{userinfo}@{host}:{}", node.port);
+    if !plugin.is_empty() {
+        let plugin = if plugin_opts.is_empty() {
+            plugin.clone()
+        } else {
+            format!("{plugin};{plugin_opts}")
+        };
+        link.push_str(&format!(
+            "/?plugin={}",
+            utf8_percent_encode(&plugin, NON_ALPHANUMERIC)
+        ));
+    }
+    if !node.name.is_empty() {
+        link.push('#');
+        link.push_str(&utf8_percent_encode(&node.name, NON_ALPHANUMERIC).to_string());
+    }
+    link
+}
+
+/// Normalize a cipher name and make sure xray supports it
+pub fn check_method(method: &str) -> Result<String> {
+    let method = method.to_lowercase();
+    if !SUPPORTED_METHODS.contains(&method.as_str()) {
+        return Err(anyhow!("Unsupported ss cipher: {method}"));
+    }
+    Ok(method)
+}
+
+/// Parse a SIP008 JSON document, returning one result per `servers` entry
+pub fn parse_sip008(document: &serde_json::Value) -> Result<Vec<Result<Node>>> {
+    let servers = document
+        .get("servers")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| anyhow!("SIP008 document has no servers list"))?;
+
+    Ok(servers.iter().map(sip008_server_to_node).collect())
+}
+
+/// Convert one SIP008 server entry into a Node
+fn sip008_server_to_node(server: &serde_json::Value) -> Result<Node> {
+    let get = |key| {
+        server
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let name = get("remarks");
+    let address = get("server");
+    if address.is_empty() {
+        return Err(anyhow!("Missing server for SIP008 entry {name}"));
+    }
+    let port = server
+        .get("server_port")
+        .and_then(|p| p.as_u64())
+        .and_then(|p| u16::try_from(p).ok())
+        .ok_or_else(|| anyhow!("Invalid server_port for SIP008 entry {name}"))?;
+
+    Ok(Node::new(
+        name,
+        address,
+        port,
+        ProtocolSettings::Shadowsocks {
+            method: check_method(&get("method"))?,
+            password: get("password"),
+            plugin: get("plugin"),
+            plugin_opts: get("plugin_opts"),
+        },
+    ))
+}
+
+/// Decode SIP002 user info, which is base64 for classic ciphers and
+/// percent-encoded plain text for 2022 ciphers
+fn decode_userinfo(userinfo: &str) -> Result<String> {
+    let plain = percent_decode_str(userinfo).decode_utf8_lossy().to_string();
+    if plain.contains(':') {
+        return Ok(plain);
+    }
+    let decoded = decode_base64(&plain)?;
+    String::from_utf8(decoded).map_err(|e| anyhow!("UTF-8 decode error: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sip002_link() {
+        let userinfo = general_purpose::URL_SAFE_NO_PAD.encode("aes-256-gcm:secret");
+        let link = format!(
+            "ss://{userinfo}@ss.test.com:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dcdn.test.com#SS%20Node"
+        );
+
+        let node = from_link(&link).unwrap();
+        assert_eq!(node.name, "SS Node");
+        assert_eq!(node.address, "ss.test.com");
+        assert_eq!(node.port, 8388);
+        assert_eq!(
+            node.settings,
+            ProtocolSettings::Shadowsocks {
+                method: "aes-256-gcm".to_string(),
+                password: "secret".to_string(),
+                plugin: "obfs-local".to_string(),
+                plugin_opts: "obfs=http;obfs-host=cdn.test.com".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_and_2022_links() {
+        let legacy = format!(
+            "ss://{}#Legacy",
+            general_purpose::STANDARD.encode("chacha20-ietf-poly1305:pass@1.2.3.4:443")
+        );
+        let node = from_link(&legacy).unwrap();
+        assert_eq!(node.name, "Legacy");
+        assert_eq!(node.address, "1.2.3.4");
+        assert!(matches!(
+            &node.settings,
+            ProtocolSettings::Shadowsocks { method, .. } if method == "chacha20-ietf-poly1305"
+        ));
+        assert_eq!(node.credential(), "pass");
+
+        let ss2022 = "ss://2022-blake3-aes-128-gcm:YctPZ6U7xPPcU%2Bgp3u%2B0tx%2FtRizJN9K8y%2BuKlW2qjlI%3D@[::1]:8443#SS2022";
+        let node = from_link(ss2022).unwrap();
+        assert_eq!(node.address, "::1");
+        assert!(matches!(
+            &node.settings,
+            ProtocolSettings::Shadowsocks { method, .. } if method == "2022-blake3-aes-128-gcm"
+        ));
+        assert_eq!(
+            node.credential(),
+            "YctPZ6U7xPPcU+gp3u+0tx/tRizJN9K8y+uKlW2qjlI="
+        );
+
+        let unsupported = format!(
+            "ss://{}@1.2.3.4:443",
+            general_purpose::STANDARD.encode("rc4-md5:pass")
+        );
+        assert!(from_link(&unsupported).is_err());
+    }
+
+    #[test]
+    fn test_parse_sip008() {
+        let document = serde_json::json!({
+            "version": 1,
+            "servers": [
+                {
+                    "id": "27b8a625-4f4b-4428-9f0f-8a2317db7c79",
+                    "remarks": "Server 1",
+                    "server": "s1.test.com",
+                    "server_port": 8388,
+                    "password": "pass",
+                    "method": "chacha20-ietf-poly1305",
+                    "plugin": "v2ray-plugin",
+                    "plugin_opts": "server"
+                },
+                {
+                    "remarks": "Bad cipher",
+                    "server": "s2.test.com",
+                    "server_port": 8388,
+                    "password": "pass",
+                    "method": "rc4-md5"
+                }
+            ]
+        });
+
+        let results = parse_sip008(&document).unwrap();
+        assert_eq!(results.len(), 2);
+        let node = results[0].as_ref().unwrap();
+        assert_eq!(node.name, "Server 1");
+        assert_eq!(node.port, 8388);
+        assert!(results[1].is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::export::with_unique_names;
+use crate::node::{Node, ProtocolSettings, RealitySettings, Security, TlsSettings, Transport};
+use crate::shadowsocks::check_method;
+
+/// Outbound types that route traffic locally instead of describing a server
+const NON_PROXY_TYPES: &[&str] = &["direct", "block", "dns", "selector", "urltest"];
+
+/// Parse a sing-box config, returning one result per proxy in `outbounds`
+pub fn parse_singbox(document: &Value) -> Result<Vec<Result<Node>>> {
+    let outbounds = document
+        .get("outbounds")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("sing-box document has no outbounds list"))?;
+
+    Ok(outbounds
+        .iter()
+        .filter(|o| !NON_PROXY_TYPES.contains(&str_field(o, "type").as_str()))
+        .map(outbound_to_node)
+        .collect())
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Read a field that may be a single string or a list of strings, joined by commas
+fn list_field(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(","),
+        Some(Value::String(s)) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Convert one sing-box outbound into a Node
+fn outbound_to_node(outbound: &Value) -> Result<Node> {
+    let kind = str_field(outbound, "type");
+    let name = str_field(outbound, "tag");
+    let address = str_field(outbound, "server");
+    if address.is_empty() {
+        return Err(anyhow!("Missing server for outbound {name}"));
+    }
+    let port = outbound
+        .get("server_port")
+        .and_then(Value::as_u64)
+        .and_then(|p| u16::try_from(p).ok())
+        .ok_or_else(|| anyhow!("Invalid server_port for outbound {name}"))?;
+
+    let settings = match kind.as_str() {
+        "vmess" => ProtocolSettings::Vmess {
+            id: str_field(outbound, "uuid"),
+            alter_id: outbound
+                .get("alter_id")
+                .and_then(Value::as_u64)
+                .unwrap_or(0) as u32,
+        },
+        "vless" => ProtocolSettings::Vless {
+            id: str_field(outbound, "uuid"),
+            flow: str_field(outbound, "flow"),
+            encryption: String::new(),
+        },
+        "trojan" => ProtocolSettings::Trojan {
+            password: str_field(outbound, "password"),
+        },
+        "shadowsocks" => ProtocolSettings::Shadowsocks {
+            method: check_method(&str_field(outbound, "method"))?,
+            password: str_field(outbound, "password"),
+            plugin: str_field(outbound, "plugin"),
+            plugin_opts: str_field(outbound, "plugin_opts"),
+        },
+        other => return Err(anyhow!("Unsupported outbound type {other:?} for {name}")),
+    };
+
+    let mut node = Node::new(name, address, port, settings);
+    if let Some(transport) = outbound.get("transport") {
+        node.transport = map_transport(transport);
+    }
+    if let Some(tls) = outbound.get("tls") {
+        node.security = map_tls(tls);
+    }
+    Ok(node)
+}
+
+/// Map a sing-box transport object to a Transport
+fn map_transport(transport: &Value) -> Transport {
+    let kind = str_field(transport, "type");
+    let host = match kind.as_str() {
+        "ws" => transport
+            .get("headers")
+            .map(|h| list_field(h, "Host"))
+            .unwrap_or_default(),
+        _ => list_field(transport, "host"),
+    };
+
+    Transport {
+        // sing-box "http" is HTTP/2 when combined with TLS
+        network: match kind.as_str() {
+            "http" => "h2".to_string(),
+            _ => kind,
+        },
+        header_type: String::new(),
+        host,
+        path: str_field(transport, "path"),
+        service_name: str_field(transport, "service_name"),
+    }
+}
+
+/// Map a sing-box tls object to a Security layer
+fn map_tls(tls: &Value) -> Security {
+    if !tls.get("enabled").and_then(Value::as_bool).unwrap_or(false) {
+        return Security::None;
+    }
+
+    let sni = str_field(tls, "server_name");
+    let fingerprint = tls
+        .get("utls")
+        .map(|u| str_field(u, "fingerprint"))
+        .unwrap_or_default();
+
+    match tls.get("reality") {
+        Some(reality) if reality.get("enabled").and_then(Value::as_bool) == Some(true) => {
+            Security::Reality(RealitySettings {
+                sni,
+                fingerprint,
+                public_key: str_field(reality, "public_key"),
+                short_id: str_field(reality, "short_id"),
+                spider_x: String::new(),
+            })
+        }
+        _ => Security::Tls(TlsSettings {
+            sni,
+            alpn: list_field(tls, "alpn"),
+            fingerprint,
+            allow_insecure: tls.get("insecure").and_then(Value::as_bool) == Some(true),
+        }),
+    }
+}
+
+/// Build a sing-box config with the nodes as outbounds behind a selector
+pub fn singbox_config(nodes: &[Node]) -> Value {
+    let nodes = &with_unique_names(nodes, &["proxy", "direct"]);
+    let tags: Vec<String> = nodes.iter().map(Node::display_name).collect();
+    let mut outbounds = vec![json!({ "type": "selector", "tag": "proxy", "outbounds": tags })];
+    outbounds.extend(nodes.iter().map(node_to_outbound));
+    outbounds.push(json!({ "type": "direct", "tag": "direct" }));
+    json!({ "outbounds": outbounds })
+}
+
+/// Convert a Node into a sing-box outbound
+pub fn node_to_outbound(node: &Node) -> Value {
+    let mut outbound = json!({
+        "tag": node.display_name(),
+        "server": node.address,
+        "server_port": node.port,
+    });
+    let fields = match &node.settings {
+        ProtocolSettings::Vmess { id, alter_id } => json!({
+            "type": "vmess",
+            "uuid": id,
+            "alter_id": alter_id,
+            "security": "auto",
+        }),
+        ProtocolSettings::Vless { id, flow, .. } => json!({
+            "type": "vless",
+            "uuid": id,
+            "flow": flow,
+        }),
+        ProtocolSettings::Trojan { password } => json!({
+            "type": "trojan",
+            "password": password,
+        }),
+        ProtocolSettings::Shadowsocks {
+            method,
+            password,
+            plugin,
+            plugin_opts,
+        } => json!({
+            "type": "shadowsocks",
+            "method": method,
+            "password": password,
+            // sing-box only knows the SIP003 names obfs-local and v2ray-plugin
+            "plugin": if plugin == "simple-obfs" { "obfs-local" } else { plugin },
+            "plugin_opts": plugin_opts,
+        }),
+    };
+    if let (Some(outbound), Value::Object(fields)) = (outbound.as_object_mut(), fields) {
+        outbound.extend(fields.into_iter().filter(|(_, v)| v != ""));
+        if let Some(transport) = transport_value(&node.transport) {
+            outbound.insert("transport".to_string(), transport);
+        }
+        if let Some(tls) = tls_value(&node.security) {
+            outbound.insert("tls".to_string(), tls);
+        }
+    }
+    outbound
+}
+
+/// Split a comma-separated value into a list
+fn split_list(value: &str) -> Vec<&str> {
+    value.split(',').filter(|s| !s.is_empty()).collect()
+}
+
+/// sing-box transport object of a Transport, None for plain TCP
+fn transport_value(transport: &Transport) -> Option<Value> {
+    let t = transport;
+    match t.network.as_str() {
+        "ws" => Some(json!({ "type": "ws", "path": t.path, "headers": { "Host": t.host } })),
+        "httpupgrade" => Some(json!({ "type": "httpupgrade", "host": t.host, "path": t.path })),
+        "grpc" => Some(json!({ "type": "grpc", "service_name": t.service_name })),
+        "h2" => Some(json!({ "type": "http", "host": split_list(&t.host), "path": t.path })),
+        _ => None,
+    }
+}
+
+/// sing-box tls object of a Security layer, None without TLS
+fn tls_value(security: &Security) -> Option<Value> {
+    match security {
+        Security::None => None,
+        Security::Tls(tls) => {
+            let mut value = json!({
+                "enabled": true,
+                "server_name": tls.sni,
+                "alpn": split_list(&tls.alpn),
+                "insecure": tls.allow_insecure,
+            });
+            if !tls.fingerprint.is_empty() {
+                value["utls"] = json!({ "enabled": true, "fingerprint": tls.fingerprint });
+            }
+            Some(value)
+        }
+        Security::Reality(reality) => Some(json!({
+            "enabled": true,
+            "server_name": reality.sni,
+            "utls": { "enabled": true, "fingerprint": reality.fingerprint },
+            "reality": {
+                "enabled": true,
+                "public_key": reality.public_key,
+                "short_id": reality.short_id,
+            },
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Protocol;
+
+    #[test]
+    fn test_parse_singbox() {
+        let document = json!({
+            "outbounds": [
+                {
+                    "type": "vless",
+                    "tag": "reality",
+                    "server": "1.2.3.4",
+                    "server_port": 443,
+                    "uuid": "uuid",
+                    "flow": "xtls-rprx-vision",
+                    "tls": {
+                        "enabled": true,
+                        "server_name": "www.microsoft.com",
+                        "utls": { "enabled": true, "fingerprint": "chrome" },
+                        "reality": { "enabled": true, "public_key": "pubkey", "short_id": "ab" }
+                    }
+                },
+                {
+                    "type": "vmess",
+                    "tag": "ws",
+                    "server": "v.test.com",
+                    "server_port": 443,
+                    "uuid": "uuid",
+                    "transport": { "type": "ws", "path": "/ws", "headers": { "Host": "cdn.test.com" } }
+                },
+                { "type": "hysteria2", "tag": "hy2", "server": "h.test.com", "server_port": 443 },
+                { "type": "direct", "tag": "direct" },
+                { "type": "selector", "tag": "proxy", "outbounds": ["reality", "ws"] }
+            ]
+        });
+
+        let results = parse_singbox(&document).unwrap();
+        assert_eq!(results.len(), 3);
+
+        let vless = results[0].as_ref().unwrap();
+        assert_eq!(vless.protocol(), Protocol::Vless);
+        let Security::Reality(reality) = &vless.security else {
+            panic!("expected reality security");
+        };
+        assert_eq!(reality.public_key, "pubkey");
+        assert_eq!(reality.fingerprint, "chrome");
+
+        let vmess = results[1].as_ref().unwrap();
+        assert_eq!(vmess.transport.network, "ws");
+        assert_eq!(vmess.transport.host, "cdn.test.com");
+        assert_eq!(vmess.security, Security::None);
+
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_singbox_config_roundtrip() {
+        let links = [
+            "vless://uuid@1.2.3.4:443?encryption=none&flow=xtls-rprx-vision&security=reality&sni=a.com&fp=chrome&pbk=key&sid=ab#Reality",
+            "trojan://p@t.test.com:443?sni=t.test.com&alpn=h2,http/1.1&type=ws&path=%2Fws&host=cdn.test.com#WS",
+            "vless://uuid@g.test.com:443?encryption=none&security=tls&sni=g.test.com&type=grpc&serviceName=svc#gRPC",
+        ];
+        let nodes: Vec<Node> = links.iter().map(|l| Node::from_link(l).unwrap()).collect();
+
+        let config = singbox_config(&nodes);
+        let restored: Vec<Node> = parse_singbox(&config)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(restored.len(), nodes.len());
+        for (restored, node) in restored.iter().zip(&nodes) {
+            assert_eq!(restored.fingerprint(), node.fingerprint());
+            assert_eq!(restored.security, node.security);
+        }
+
+        // Tags must be unique, including the selector and direct outbounds
+        let mut direct = nodes[1].clone();
+        direct.name = "direct".to_string();
+        let config = singbox_config(&[nodes[1].clone(), nodes[1].clone(), direct]);
+        let tags: Vec<&str> = config["outbounds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["tag"].as_str().unwrap())
+            .collect();
+        assert_eq!(tags, vec!["proxy", "WS", "WS (2)", "direct (2)", "direct"]);
+        assert_eq!(config["outbounds"][0]["outbounds"][1], "WS (2)");
+    }
+}
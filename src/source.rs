@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use crate::node::Node;
+use crate::notice::InfoRules;
+use crate::subscribe::{parse_subscription_content, IssueKind, ParseIssue, ParseReport};
+
+/// Subscription source read from the local machine instead of over HTTP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalSource {
+    /// `-`: subscription piped into standard input, read once
+    Stdin,
+    /// `file://` path to a file, or to a directory whose files are merged
+    Path(PathBuf),
+}
+
+/// Modification state of a local source, compared to detect changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceStamp(Vec<(PathBuf, Option<SystemTime>, u64)>);
+
+/// Standard input, kept since it can only be read once
+static STDIN: OnceLock<Result<String, String>> = OnceLock::new();
+
+impl LocalSource {
+    /// Recognize `-` and `file://` URLs; None for remote URLs
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim();
+        if url == "-" {
+            return Some(LocalSource::Stdin);
+        }
+        let path = url.strip_prefix("file://")?;
+        let path = percent_decode_str(path).decode_utf8_lossy();
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()?.join(rest),
+            None => PathBuf::from(path.as_ref()),
+        };
+        Some(LocalSource::Path(path))
+    }
+
+    /// Whether the source can be watched for changes
+    pub fn is_watchable(&self) -> bool {
+        matches!(self, LocalSource::Path(_))
+    }
+
+    /// Current modification state, None for stdin or an unreadable path
+    pub fn stamp(&self) -> Option<SourceStamp> {
+        let LocalSource::Path(path) = self else {
+            return None;
+        };
+        let files = if path.is_dir() {
+            directory_files(path).ok()?
+        } else {
+            vec![path.clone()]
+        };
+        let entries = files
+            .into_iter()
+            .map(|file| {
+                let meta = fs::metadata(&file).ok();
+                let modified = meta.as_ref().and_then(|m| m.modified().ok());
+                let len = meta.map(|m| m.len()).unwrap_or(0);
+                (file, modified, len)
+            })
+            .collect();
+        Some(SourceStamp(entries))
+    }
+
+    /// Read and parse the source; every file of a directory is parsed on its own
+    /// and the nodes are merged in file name order
+    pub fn load(&self, rules: &InfoRules) -> Result<(Vec<Node>, ParseReport)> {
+        match self {
+            LocalSource::Stdin => parse_subscription_content(&read_stdin()?, rules),
+            LocalSource::Path(path) if path.is_dir() => load_directory(path, rules),
+            LocalSource::Path(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+                parse_subscription_content(&content, rules)
+            }
+        }
+    }
+}
+
+/// Read all of standard input on first use
+fn read_stdin() -> Result<String> {
+    STDIN
+        .get_or_init(|| {
+            let mut stdin = std::io::stdin();
+            if stdin.is_terminal() {
+                return Err("Standard input is a terminal, pipe a subscription into subman".into());
+            }
+            let mut content = String::new();
+            stdin
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Failed to read standard input: {e}"))?;
+            Ok(content)
+        })
+        .clone()
+        .map_err(|e| anyhow!(e))
+}
+
+/// Regular, non-hidden files of a directory sorted by name
+fn directory_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Parse every file of a directory; a file that fails is reported as an issue
+fn load_directory(dir: &Path, rules: &InfoRules) -> Result<(Vec<Node>, ParseReport)> {
+    let mut nodes = Vec::new();
+    let mut report: Option<ParseReport> = None;
+    let mut failures = Vec::new();
+
+    for file in directory_files(dir)? {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parsed = fs::read_to_string(&file)
+            .map_err(|e| anyhow!("{e}"))
+            .and_then(|content| parse_subscription_content(&content, rules));
+        match parsed {
+            Ok((file_nodes, file_report)) => {
+                nodes.extend(file_nodes);
+                report
+                    .get_or_insert_with(|| ParseReport {
+                        format: file_report.format,
+                        ..Default::default()
+                    })
+                    .merge(&name, file_report);
+            }
+            Err(e) => failures.push(ParseIssue {
+                line: 0,
+                prefix: name,
+                kind: IssueKind::InvalidEntry,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    let Some(mut report) = report else {
+        let details: Vec<String> = failures
+            .iter()
+            .map(|f| format!("{}: {}", f.prefix, f.message))
+            .collect();
+        return Err(anyhow!(
+            "No nodes found in {}{}",
+            dir.display(),
+            if details.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", details.join("; "))
+            }
+        ));
+    };
+    report.invalid += failures.len();
+    report.issues.extend(failures);
+    Ok((nodes, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_local_source() {
+        assert_eq!(LocalSource::parse("-"), Some(LocalSource::Stdin));
+        assert_eq!(
+            LocalSource::parse("file:///srv/my%20nodes.txt"),
+            Some(LocalSource::Path(PathBuf::from("/srv/my nodes.txt")))
+        );
+        assert_eq!(LocalSource::parse("https://a.test.com/sub"), None);
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let dir = std::env::temp_dir().join(format!("subman-source-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "trojan://p@a.test.com:443#A\n").unwrap();
+        fs::write(dir.join("b.txt"), "trojan://p@b.test.com:443#B\n").unwrap();
+        fs::write(dir.join("notes.md"), "# servers\n").unwrap();
+        fs::write(dir.join(".hidden"), "trojan://p@c.test.com:443#C\n").unwrap();
+
+        let source = LocalSource::Path(dir.clone());
+        let (nodes, report) = source.load(&InfoRules::default()).unwrap();
+        let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(report.recognized, 2);
+        assert_eq!(report.invalid, 1);
+        assert_eq!(report.issues[0].prefix, "notes.md");
+
+        let stamp = source.stamp().unwrap();
+        assert_eq!(source.stamp(), Some(stamp.clone()));
+        fs::write(dir.join("b.txt"), "trojan://p@b.test.com:443#B2\n").unwrap();
+        assert_ne!(source.stamp(), Some(stamp));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,373 @@
 use anyhow::{anyhow, Result};
-use base64::{engine::general_purpose, Engine};
+use reqwest::header::{
+    HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, USER_AGENT,
+};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
 
-use crate::vmess::VmessNode;
+use crate::cache::{fnv1a, CachedBody};
+use crate::clash::{is_clash_yaml, parse_clash_yaml};
+use crate::node::{decode_base64, Node};
+use crate::notice::InfoRules;
+use crate::shadowsocks::parse_sip008;
+use crate::singbox::parse_singbox;
+use crate::source::LocalSource;
+use crate::userinfo::UserInfo;
+use crate::xray::DEFAULT_SOCKS_PORT;
 
-/// Fetch subscription content from URL and parse into vmess nodes
-pub async fn fetch_subscription(url: &str) -> Result<Vec<VmessNode>> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()?;
+/// User-Agent sent when a subscription doesn't set its own
+pub const DEFAULT_USER_AGENT: &str = "subman/0.1.0";
+/// Request timeout when a subscription doesn't set its own
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
-    let response = client
-        .get(url)
-        .header("User-Agent", "subman/0.1.0")
-        .send()
-        .await
-        .map_err(|e| anyhow!("Failed to fetch subscription: {e}"))?;
+/// Network path used to download a subscription
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum FetchMode {
+    /// Connect to the provider without a subman-configured proxy; the
+    /// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment
+    /// variables still apply
+    Direct,
+    /// Through the SOCKS inbound of the local xray instance
+    Xray,
+    /// Through an HTTP or SOCKS5 proxy URL
+    Proxy(String),
+}
+
+impl FromStr for FetchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "direct" => Ok(FetchMode::Direct),
+            "xray" => Ok(FetchMode::Xray),
+            url if ["http://", "https://", "socks5://", "socks5h://"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme)) =>
+            {
+                reqwest::Proxy::all(url).map_err(|e| anyhow!("Invalid proxy {url:?}: {e}"))?;
+                Ok(FetchMode::Proxy(url.to_string()))
+            }
+            other => Err(anyhow!(
+                "Unknown fetch mode {other:?} (use direct, xray or an http/socks5 proxy URL)"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for FetchMode {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<FetchMode> for String {
+    fn from(mode: FetchMode) -> Self {
+        mode.to_string()
+    }
+}
+
+impl fmt::Display for FetchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchMode::Direct => write!(f, "direct"),
+            FetchMode::Xray => write!(f, "xray"),
+            FetchMode::Proxy(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+impl FetchMode {
+    /// Parse a comma-separated fallback list such as "direct, xray"
+    pub fn parse_list(s: &str) -> Result<Vec<FetchMode>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// Format a fallback list as accepted by `parse_list`
+    pub fn format_list(modes: &[FetchMode]) -> String {
+        modes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// HTTP client routed through this path
+    fn client(&self, timeout: Duration) -> Result<reqwest::Client> {
+        let builder = reqwest::Client::builder().timeout(timeout);
+        let builder = match self {
+            FetchMode::Direct => builder,
+            FetchMode::Xray => builder.proxy(reqwest::Proxy::all(format!(
+                "socks5h://127.0.0.1:{DEFAULT_SOCKS_PORT}"
+            ))?),
+            FetchMode::Proxy(url) => builder.proxy(reqwest::Proxy::all(url)?),
+        };
+        Ok(builder.build()?)
+    }
+}
+
+/// Credentials for HTTP Basic authentication
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BasicAuth {
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+/// Per-subscription HTTP request settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestOptions {
+    /// User-Agent header, `DEFAULT_USER_AGENT` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Extra request headers such as Cookie or Authorization
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basic_auth: Option<BasicAuth>,
+    /// Request timeout in seconds per fetch path, `DEFAULT_TIMEOUT_SECS` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Whether text is a header name token (RFC 9110 `tchar`s, no spaces)
+fn is_header_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+impl RequestOptions {
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    /// Parse "Name: value; Name: value" into validated headers. A segment
+    /// that does not start with a header name continues the previous value,
+    /// so "Cookie: a=1; b=2" stays one header.
+    pub fn parse_headers(s: &str) -> Result<BTreeMap<String, String>> {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        for segment in s.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let header = segment
+                .split_once(':')
+                .filter(|(name, _)| is_header_name(name.trim_start()));
+            match (header, pairs.last_mut()) {
+                (Some((name, value)), _) => {
+                    pairs.push((name.trim().to_string(), value.trim().to_string()))
+                }
+                (None, Some((_, value))) => {
+                    value.push_str("; ");
+                    value.push_str(segment);
+                }
+                (None, None) => {
+                    return Err(anyhow!("Header {segment:?} is not in Name: value form"));
+                }
+            }
+        }
+
+        let mut headers = BTreeMap::new();
+        for (name, value) in pairs {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("Invalid header name {name:?}"))?;
+            HeaderValue::from_str(&value)
+                .map_err(|_| anyhow!("Invalid value for header {name}"))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// Format headers as accepted by `parse_headers`
+    pub fn format_headers(headers: &BTreeMap<String, String>) -> String {
+        headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Parse "username:password" (password optional), None when empty
+    pub fn parse_basic_auth(s: &str) -> Option<BasicAuth> {
+        let s = s.trim();
+        if s.is_empty() {
+            return None;
+        }
+        let (username, password) = s.split_once(':').unwrap_or((s, ""));
+        Some(BasicAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    /// Format basic auth as accepted by `parse_basic_auth`
+    pub fn format_basic_auth(auth: Option<&BasicAuth>) -> String {
+        auth.map(|a| format!("{}:{}", a.username, a.password))
+            .unwrap_or_default()
+    }
+
+    /// Key of the options that change the response body, for conditional
+    /// requests: a body fetched with other options is downloaded again
+    pub fn cache_key(&self) -> u64 {
+        let options = (&self.user_agent, &self.headers, &self.basic_auth);
+        fnv1a(&serde_json::to_string(&options).unwrap_or_default())
+    }
+
+    /// Apply User-Agent, extra headers and credentials to a request
+    fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        request = request.header(
+            USER_AGENT,
+            self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT),
+        );
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(auth) = &self.basic_auth {
+            request = request.basic_auth(&auth.username, Some(&auth.password));
+        }
+        request
+    }
+}
+
+/// A subscription to fetch, keyed by its index in the subscription list
+#[derive(Debug, Clone)]
+pub struct FetchTarget {
+    pub index: usize,
+    pub url: String,
+    /// Paths to try in order, direct when empty
+    pub via: Vec<FetchMode>,
+    pub request: RequestOptions,
+}
+
+/// Nodes and metadata from one successful subscription fetch
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    pub nodes: Vec<Node>,
+    pub report: ParseReport,
+    /// Quota and expiry from the `subscription-userinfo` header
+    pub userinfo: Option<UserInfo>,
+    /// Server answered 304 Not Modified and the cached body was parsed instead
+    pub not_modified: bool,
+    /// Path that succeeded
+    pub via: FetchMode,
+}
+
+/// Response of a single download attempt
+struct Download {
+    userinfo: Option<UserInfo>,
+    /// New body, None when the server answered 304 Not Modified
+    body: Option<CachedBody>,
+}
+
+/// Fetch subscription content from URL and parse into nodes.
+///
+/// Tries each path of `via` in order until one yields nodes. Sends
+/// `If-None-Match`/`If-Modified-Since` when a cached body exists, reuses that
+/// body on 304, and caches every body that parses successfully.
+pub async fn fetch_subscription(
+    url: &str,
+    via: &[FetchMode],
+    options: &RequestOptions,
+    rules: &InfoRules,
+) -> Result<Fetched> {
+    if let Some(source) = LocalSource::parse(url) {
+        return fetch_local(source, rules).await;
+    }
+
+    // Validators of a body fetched with other request options are not sent;
+    // `load_cached` still falls back to that body
+    let cached = CachedBody::load(url).filter(|c| c.request_key == options.cache_key());
+    let direct = [FetchMode::Direct];
+    let modes = if via.is_empty() { &direct[..] } else { via };
+
+    let mut failures = Vec::new();
+    for mode in modes {
+        let download = match download(url, mode, options, cached.as_ref()).await {
+            Ok(download) => download,
+            Err(e) => {
+                failures.push(format!("{mode}: {e}"));
+                continue;
+            }
+        };
+
+        let not_modified = download.body.is_none();
+        let Some(body) = download.body.as_ref().or(cached.as_ref()) else {
+            continue;
+        };
+        // A blocked path may answer with an HTML page instead of the subscription
+        let (nodes, report) = match parse_subscription_content(&body.body, rules) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                failures.push(format!("{mode}: {e}"));
+                continue;
+            }
+        };
+
+        // A cache write failure only costs the next conditional request
+        if let Some(body) = &download.body {
+            let _ = body.save(url);
+        }
+
+        return Ok(Fetched {
+            nodes,
+            report,
+            userinfo: download.userinfo,
+            not_modified,
+            via: mode.clone(),
+        });
+    }
+
+    Err(anyhow!(
+        "Failed to fetch subscription: {}",
+        failures.join("; ")
+    ))
+}
+
+/// Download a subscription body through one path
+async fn download(
+    url: &str,
+    mode: &FetchMode,
+    options: &RequestOptions,
+    cached: Option<&CachedBody>,
+) -> Result<Download> {
+    let client = mode.client(options.timeout())?;
+    let mut request = options.apply(client.get(url));
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let userinfo =
+        header(HeaderName::from_static("subscription-userinfo")).and_then(|v| UserInfo::parse(&v));
+
+    if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+        return Ok(Download {
+            userinfo,
+            body: None,
+        });
+    }
 
     if !response.status().is_success() {
         return Err(anyhow!(
@@ -24,64 +377,372 @@ pub async fn fetch_subscription(url: &str) -> Result<Vec<VmessNode>> {
         ));
     }
 
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
     let body = response
         .text()
         .await
         .map_err(|e| anyhow!("Failed to read response body: {e}"))?;
 
-    parse_subscription_content(&body)
+    Ok(Download {
+        userinfo,
+        body: Some(CachedBody {
+            etag,
+            last_modified,
+            request_key: options.cache_key(),
+            body,
+        }),
+    })
+}
+
+/// Read a file, directory or stdin source without blocking the runtime
+async fn fetch_local(source: LocalSource, rules: &InfoRules) -> Result<Fetched> {
+    let rules = rules.clone();
+    let (nodes, report) = tokio::task::spawn_blocking(move || source.load(&rules)).await??;
+    Ok(Fetched {
+        nodes,
+        report,
+        userinfo: None,
+        not_modified: false,
+        via: FetchMode::Direct,
+    })
 }
 
-/// Parse base64-encoded subscription content into vmess nodes
-pub fn parse_subscription_content(content: &str) -> Result<Vec<VmessNode>> {
+/// Parse the last good body of a subscription from the local cache
+pub fn load_cached(url: &str, rules: &InfoRules) -> Option<(Vec<Node>, ParseReport)> {
+    let cached = CachedBody::load(url)?;
+    parse_subscription_content(&cached.body, rules).ok()
+}
+
+/// Result of fetching one subscription, keyed by its index in the subscription list
+pub type FetchOutcome = (usize, Result<Fetched>);
+
+/// Fetch several subscriptions concurrently, returning outcomes in input order
+pub async fn fetch_all(targets: Vec<FetchTarget>, rules: &InfoRules) -> Vec<FetchOutcome> {
+    let fetches = targets.into_iter().map(|target| {
+        let rules = rules.clone();
+        let fetch = async move {
+            fetch_subscription(&target.url, &target.via, &target.request, &rules).await
+        };
+        (target.index, fetch)
+    });
+    join_fetches(fetches).await
+}
+
+/// Run fetches concurrently; a fetch task that panics or is cancelled still
+/// yields an error outcome for its subscription
+async fn join_fetches<F>(fetches: impl IntoIterator<Item = (usize, F)>) -> Vec<FetchOutcome>
+where
+    F: Future<Output = Result<Fetched>> + Send + 'static,
+{
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut indices = HashMap::new();
+    for (index, fetch) in fetches {
+        let handle = tasks.spawn(fetch);
+        indices.insert(handle.id(), index);
+    }
+
+    let mut outcomes = Vec::new();
+    while let Some(joined) = tasks.join_next_with_id().await {
+        let outcome = match joined {
+            Ok((id, result)) => (indices[&id], result),
+            Err(e) => (indices[&e.id()], Err(anyhow!("Fetch task failed: {e}"))),
+        };
+        outcomes.push(outcome);
+    }
+    outcomes.sort_by_key(|(index, _)| *index);
+    outcomes
+}
+
+/// Subscription body formats recognized by `detect_format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubscriptionFormat {
+    /// Clash/mihomo YAML profile with a `proxies` list
+    ClashYaml,
+    /// sing-box config with an `outbounds` list
+    SingBox,
+    /// SIP008 Shadowsocks JSON with a `servers` list
+    Sip008,
+    /// Whole body is a base64-encoded list of share links
+    #[default]
+    Base64Links,
+    /// Newline-separated share links in clear text
+    PlainLinks,
+    /// Clear-text links mixed with lines that are base64 blobs of links
+    MixedLinks,
+}
+
+impl SubscriptionFormat {
+    /// Human-readable format name
+    pub fn name(self) -> &'static str {
+        match self {
+            SubscriptionFormat::ClashYaml => "Clash YAML",
+            SubscriptionFormat::SingBox => "sing-box JSON",
+            SubscriptionFormat::Sip008 => "SIP008 JSON",
+            SubscriptionFormat::Base64Links => "base64 links",
+            SubscriptionFormat::PlainLinks => "plain links",
+            SubscriptionFormat::MixedLinks => "mixed links",
+        }
+    }
+}
+
+/// Why a subscription line or entry did not produce a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Share link with a scheme subman does not handle
+    UnsupportedScheme,
+    /// Share link of a supported protocol that failed to parse
+    InvalidLink,
+    /// Proxy entry of a YAML/JSON document that failed to convert
+    InvalidEntry,
+}
+
+impl IssueKind {
+    /// Short label for the report popup
+    pub fn label(self) -> &'static str {
+        match self {
+            IssueKind::UnsupportedScheme => "unsupported",
+            IssueKind::InvalidLink => "invalid link",
+            IssueKind::InvalidEntry => "invalid entry",
+        }
+    }
+}
+
+/// A single line or entry that was skipped or rejected
+#[derive(Debug, Clone)]
+pub struct ParseIssue {
+    /// 1-based line number, or entry index for YAML/JSON documents
+    pub line: usize,
+    /// Leading part of the offending link, empty for YAML/JSON entries
+    pub prefix: String,
+    pub kind: IssueKind,
+    /// Parser error message
+    pub message: String,
+}
+
+/// Summary of how a subscription body was parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    /// Detected body format
+    pub format: SubscriptionFormat,
+    /// Lines or entries turned into nodes
+    pub recognized: usize,
+    /// Lines that are not share links of a supported protocol
+    pub skipped: usize,
+    /// Share links or entries that failed to parse
+    pub invalid: usize,
+    /// Details for every unsupported or invalid line
+    pub issues: Vec<ParseIssue>,
+    /// Text of informational entries pulled out of the node list
+    pub notices: Vec<String>,
+}
+
+impl ParseReport {
+    /// One-line summary for the status bar
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}: {} recognized, {} skipped, {} invalid",
+            self.format.name(),
+            self.recognized,
+            self.skipped,
+            self.invalid
+        );
+        if !self.notices.is_empty() {
+            summary.push_str(&format!(", {} notices", self.notices.len()));
+        }
+        summary
+    }
+
+    /// Add the counts, issues and notices of one file of a directory source
+    pub fn merge(&mut self, file: &str, other: ParseReport) {
+        self.recognized += other.recognized;
+        self.skipped += other.skipped;
+        self.invalid += other.invalid;
+        self.issues
+            .extend(other.issues.into_iter().map(|issue| ParseIssue {
+                message: format!("{file}: {}", issue.message),
+                ..issue
+            }));
+        self.notices.extend(other.notices);
+    }
+}
+
+/// Maximum number of characters of a link shown in a parse issue
+const LINK_PREFIX_LEN: usize = 24;
+
+/// Truncate a link so the report shows its scheme without leaking the whole credential
+pub fn link_prefix(line: &str) -> String {
+    let mut prefix: String = line.chars().take(LINK_PREFIX_LEN).collect();
+    if line.chars().count() > LINK_PREFIX_LEN {
+        prefix.push('…');
+    }
+    prefix
+}
+
+/// Sniff the format of a subscription body
+pub fn detect_format(content: &str) -> SubscriptionFormat {
     let content = content.trim();
+    if content.starts_with('{') {
+        if let Ok(document) = serde_json::from_str::<serde_json::Value>(content) {
+            if document.get("outbounds").is_some_and(|o| o.is_array()) {
+                return SubscriptionFormat::SingBox;
+            }
+            if document.get("servers").is_some_and(|s| s.is_array()) {
+                return SubscriptionFormat::Sip008;
+            }
+        }
+    }
+    if is_clash_yaml(content) {
+        return SubscriptionFormat::ClashYaml;
+    }
+    if !content.contains("://") && decode_base64(&compact(content)).is_ok() {
+        return SubscriptionFormat::Base64Links;
+    }
+    if content.lines().any(|line| decode_link_blob(line).is_some()) {
+        return SubscriptionFormat::MixedLinks;
+    }
+    SubscriptionFormat::PlainLinks
+}
 
-    // Try to decode as base64
-    let decoded = general_purpose::STANDARD
-        .decode(content)
-        .or_else(|_| general_purpose::URL_SAFE.decode(content))
-        .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(content))
-        .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(content))
-        .map_err(|e| anyhow!("Base64 decode error: {e}"))?;
+/// Parse subscription content (Clash YAML, sing-box/SIP008 JSON, base64, plain or mixed link lists) into nodes.
+/// Entries matching `rules` are moved out of the node list into `ParseReport::notices`.
+pub fn parse_subscription_content(
+    content: &str,
+    rules: &InfoRules,
+) -> Result<(Vec<Node>, ParseReport)> {
+    let content = content.trim();
+    let format = detect_format(content);
+    let mut report = ParseReport {
+        format,
+        ..Default::default()
+    };
 
-    let decoded_str =
-        String::from_utf8(decoded).map_err(|e| anyhow!("UTF-8 decode error: {e}"))?;
+    let mut nodes = match format {
+        SubscriptionFormat::ClashYaml => collect_nodes(parse_clash_yaml(content)?, &mut report),
+        SubscriptionFormat::SingBox => {
+            collect_nodes(parse_singbox(&parse_json(content)?)?, &mut report)
+        }
+        SubscriptionFormat::Sip008 => {
+            collect_nodes(parse_sip008(&parse_json(content)?)?, &mut report)
+        }
+        SubscriptionFormat::Base64Links => {
+            let decoded = decode_base64(&compact(content))?;
+            let decoded_str =
+                String::from_utf8(decoded).map_err(|e| anyhow!("UTF-8 decode error: {e}"))?;
+            parse_link_lines(&decoded_str, None, &mut report)
+        }
+        SubscriptionFormat::PlainLinks | SubscriptionFormat::MixedLinks => {
+            parse_link_lines(content, None, &mut report)
+        }
+    };
+
+    report.notices = rules.extract(&mut nodes);
+    report.recognized -= report.notices.len();
 
-    // Parse each line as a vmess link
+    if nodes.is_empty() {
+        return Err(anyhow!(
+            "No valid nodes found in subscription ({})",
+            report.summary()
+        ));
+    }
+
+    Ok((nodes, report))
+}
+
+fn parse_json(content: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(content).map_err(|e| anyhow!("JSON parse error: {e}"))
+}
+
+/// Remove all whitespace so wrapped base64 bodies decode as one blob
+fn compact(content: &str) -> String {
+    content.split_whitespace().collect()
+}
+
+/// Decode a line that is itself a base64 blob of one or more share links
+fn decode_link_blob(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.contains("://") {
+        return None;
+    }
+    let decoded = String::from_utf8(decode_base64(line).ok()?).ok()?;
+    decoded.contains("://").then_some(decoded)
+}
+
+/// Parse newline-separated share links, expanding lines that are base64 blobs.
+/// Lines of a nested blob are reported under the line number of the blob itself.
+fn parse_link_lines(text: &str, blob_line: Option<usize>, report: &mut ParseReport) -> Vec<Node> {
     let mut nodes = Vec::new();
-    for line in decoded_str.lines() {
+    for (i, line) in text.lines().enumerate() {
+        let line_no = blob_line.unwrap_or(i + 1);
         let line = line.trim();
         if line.is_empty() {
             continue;
         }
 
-        // Only parse vmess:// links
-        if line.starts_with("vmess://") {
-            match VmessNode::from_link(line) {
-                Ok(node) => nodes.push(node),
+        if Node::is_supported_link(line) {
+            match Node::from_link(line) {
+                Ok(node) => {
+                    nodes.push(node);
+                    report.recognized += 1;
+                }
                 Err(e) => {
-                    // Log but don't fail on individual parse errors
-                    eprintln!("Warning: Failed to parse vmess link: {e}");
+                    report.invalid += 1;
+                    report.issues.push(ParseIssue {
+                        line: line_no,
+                        prefix: link_prefix(line),
+                        kind: IssueKind::InvalidLink,
+                        message: e.to_string(),
+                    });
                 }
             }
+        } else if let Some(decoded) = decode_link_blob(line) {
+            nodes.extend(parse_link_lines(&decoded, Some(line_no), report));
+        } else {
+            report.skipped += 1;
+            if let Some((scheme, _)) = line.split_once("://") {
+                report.issues.push(ParseIssue {
+                    line: line_no,
+                    prefix: link_prefix(line),
+                    kind: IssueKind::UnsupportedScheme,
+                    message: format!("Unsupported scheme {scheme:?}"),
+                });
+            }
         }
     }
+    nodes
+}
 
-    if nodes.is_empty() {
-        return Err(anyhow!("No valid vmess nodes found in subscription"));
+/// Keep successfully parsed entries of a structured document, recording failures
+fn collect_nodes(results: Vec<Result<Node>>, report: &mut ParseReport) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(node) => {
+                nodes.push(node);
+                report.recognized += 1;
+            }
+            Err(e) => {
+                report.invalid += 1;
+                report.issues.push(ParseIssue {
+                    line: i + 1,
+                    prefix: String::new(),
+                    kind: IssueKind::InvalidEntry,
+                    message: e.to_string(),
+                });
+            }
+        }
     }
-
-    Ok(nodes)
+    nodes
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::node::Protocol;
+    use base64::{engine::general_purpose, Engine};
 
     #[test]
     fn test_parse_subscription_content() {
-        use base64::engine::general_purpose;
-
         // Create a test subscription with two nodes
         let node1_json = r#"{"ps":"Node1","add":"n1.test.com","port":443,"id":"uuid1","aid":0,"net":"tcp","type":"none","host":"","path":"","tls":"tls"}"#;
         let node2_json = r#"{"ps":"Node2","add":"n2.test.com","port":8443,"id":"uuid2","aid":0,"net":"ws","type":"none","host":"","path":"/ws","tls":"tls"}"#;
@@ -92,9 +753,210 @@ mod tests {
         let content = format!("{}\n{}", link1, link2);
         let encoded = general_purpose::STANDARD.encode(&content);
 
-        let nodes = parse_subscription_content(&encoded).unwrap();
+        let (nodes, _) = parse_subscription_content(&encoded, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].display_name(), "Node1");
+        assert_eq!(nodes[1].display_name(), "Node2");
+    }
+
+    #[test]
+    fn test_parse_mixed_protocols() {
+        let vmess_json = r#"{"ps":"VmessNode","add":"n1.test.com","port":443,"id":"uuid1","aid":0,"net":"tcp","tls":"tls"}"#;
+        let vmess_link = format!("vmess://{}", general_purpose::STANDARD.encode(vmess_json));
+        let vless_link = "vless://uuid2@n2.test.com:443?security=tls&type=ws&path=%2Fws#VlessNode";
+
+        let trojan_link = "trojan://secret@n3.test.com:443?sni=n3.test.com#TrojanNode";
+        let ss_link = format!(
+            "ss://{}@n4.test.com:8388#SSNode",
+            general_purpose::URL_SAFE_NO_PAD.encode("aes-128-gcm:secret")
+        );
+
+        let content =
+            format!("{vmess_link}\n{vless_link}\n{trojan_link}\n{ss_link}\nssr://unsupported");
+        let encoded = general_purpose::STANDARD.encode(&content);
+
+        let (nodes, _) = parse_subscription_content(&encoded, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0].protocol(), Protocol::Vmess);
+        assert_eq!(nodes[1].protocol(), Protocol::Vless);
+        assert_eq!(nodes[1].display_name(), "VlessNode");
+        assert_eq!(nodes[2].protocol(), Protocol::Trojan);
+        assert_eq!(nodes[3].protocol(), Protocol::Shadowsocks);
+    }
+
+    #[test]
+    fn test_parse_clash_subscription() {
+        let content = "proxies:\n  - {name: a, type: trojan, server: t.test.com, port: 443, password: p}\n  - {name: b, type: tuic, server: u.test.com, port: 443}\n";
+
+        let (nodes, report) = parse_subscription_content(content, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].protocol(), Protocol::Trojan);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].line, 2);
+        assert_eq!(report.issues[0].kind, IssueKind::InvalidEntry);
+    }
+
+    #[test]
+    fn test_detect_json_formats() {
+        let singbox = r#"{"outbounds":[{"type":"trojan","tag":"t","server":"t.test.com","server_port":443,"password":"p"},{"type":"direct","tag":"direct"}]}"#;
+        let sip008 = r#"{"version":1,"servers":[{"remarks":"s","server":"s.test.com","server_port":8388,"password":"p","method":"aes-256-gcm"}]}"#;
+
+        assert_eq!(detect_format(singbox), SubscriptionFormat::SingBox);
+        assert_eq!(detect_format(sip008), SubscriptionFormat::Sip008);
+
+        let (nodes, _) = parse_subscription_content(singbox, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].protocol(), Protocol::Trojan);
+
+        let (nodes, _) = parse_subscription_content(sip008, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].protocol(), Protocol::Shadowsocks);
+    }
+
+    #[test]
+    fn test_parse_plain_and_mixed_links() {
+        let trojan = "trojan://secret@t.test.com:443#T";
+        let vless = "vless://uuid@v.test.com:443?security=tls#V";
+        let blob = general_purpose::STANDARD.encode(format!("{vless}\n{trojan}"));
+
+        let plain = format!("{trojan}\n# comment\nssr://unsupported\nvless://@broken\n{vless}\n");
+        assert_eq!(detect_format(&plain), SubscriptionFormat::PlainLinks);
+        let (nodes, report) = parse_subscription_content(&plain, &InfoRules::default()).unwrap();
         assert_eq!(nodes.len(), 2);
-        assert_eq!(nodes[0].ps, "Node1");
-        assert_eq!(nodes[1].ps, "Node2");
+        assert_eq!(report.recognized, 2);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(report.invalid, 1);
+        assert_eq!(report.issues.len(), 2);
+        assert_eq!(report.issues[0].line, 3);
+        assert_eq!(report.issues[0].kind, IssueKind::UnsupportedScheme);
+        assert_eq!(report.issues[1].line, 4);
+        assert_eq!(report.issues[1].kind, IssueKind::InvalidLink);
+        assert_eq!(report.issues[1].prefix, "vless://@broken");
+
+        let mixed = format!("{trojan}\n{blob}\n");
+        assert_eq!(detect_format(&mixed), SubscriptionFormat::MixedLinks);
+        let (nodes, report) = parse_subscription_content(&mixed, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(report.recognized, 3);
+
+        // Base64 bodies wrapped across several lines still decode as one blob
+        let wrapped = general_purpose::STANDARD
+            .encode(format!("{trojan}\n{vless}"))
+            .as_bytes()
+            .chunks(16)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detect_format(&wrapped), SubscriptionFormat::Base64Links);
+        let (nodes, _) = parse_subscription_content(&wrapped, &InfoRules::default()).unwrap();
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_notices() {
+        let content = [
+            "trojan://p@1.2.3.4:443#%E5%89%A9%E4%BD%99%E6%B5%81%E9%87%8F%EF%BC%9A50GB",
+            "trojan://p@1.2.3.4:443#Expire%3A%202026-12-01",
+            "trojan://p@t.test.com:443#HK%2001",
+        ]
+        .join("\n");
+
+        let (nodes, report) = parse_subscription_content(&content, &InfoRules::defaults()).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name, "HK 01");
+        assert_eq!(report.notices, vec!["剩余流量：50GB", "Expire: 2026-12-01"]);
+        assert_eq!(report.recognized, 1);
+    }
+
+    #[test]
+    fn test_parse_fetch_modes() {
+        let modes = FetchMode::parse_list("xray, socks5://127.0.0.1:7890 ,direct").unwrap();
+        assert_eq!(
+            modes,
+            vec![
+                FetchMode::Xray,
+                FetchMode::Proxy("socks5://127.0.0.1:7890".to_string()),
+                FetchMode::Direct,
+            ]
+        );
+        assert_eq!(
+            FetchMode::format_list(&modes),
+            "xray, socks5://127.0.0.1:7890, direct"
+        );
+        assert!(FetchMode::parse_list("").unwrap().is_empty());
+        assert!(FetchMode::parse_list("direct, vpn").is_err());
+
+        let json = serde_json::to_string(&modes).unwrap();
+        assert_eq!(json, r#"["xray","socks5://127.0.0.1:7890","direct"]"#);
+    }
+
+    #[test]
+    fn test_parse_request_options() {
+        let headers =
+            RequestOptions::parse_headers("Cookie: a=b; Authorization: Bearer t ;").unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["Authorization"], "Bearer t");
+        assert_eq!(
+            RequestOptions::format_headers(&headers),
+            "Authorization: Bearer t; Cookie: a=b"
+        );
+        assert!(RequestOptions::parse_headers("no colon").is_err());
+        assert!(RequestOptions::parse_headers("Bad Name: x").is_err());
+
+        let headers = RequestOptions::parse_headers("Cookie: a=1; b=2; c=x:y; X-Token: t").unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["Cookie"], "a=1; b=2; c=x:y");
+        assert_eq!(headers["X-Token"], "t");
+        let formatted = RequestOptions::format_headers(&headers);
+        assert_eq!(RequestOptions::parse_headers(&formatted).unwrap(), headers);
+
+        let auth = RequestOptions::parse_basic_auth("user:p:w").unwrap();
+        assert_eq!(auth.username, "user");
+        assert_eq!(auth.password, "p:w");
+        assert!(RequestOptions::parse_basic_auth(" ").is_none());
+
+        // Validators of a cached body are reused only with the same options
+        let options = RequestOptions {
+            headers,
+            ..RequestOptions::default()
+        };
+        let slower = RequestOptions {
+            timeout_secs: Some(60),
+            ..options.clone()
+        };
+        assert_eq!(options.cache_key(), slower.cache_key());
+        let other_agent = RequestOptions {
+            user_agent: Some("clash".to_string()),
+            ..options.clone()
+        };
+        assert_ne!(options.cache_key(), other_agent.cache_key());
+        assert_ne!(options.cache_key(), RequestOptions::default().cache_key());
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_task_keeps_its_index() {
+        let fetches = [0, 1, 2].map(|index| {
+            let fetch = async move {
+                if index == 1 {
+                    panic!("boom");
+                }
+                Err::<Fetched, _>(anyhow!("failed {index}"))
+            };
+            (index, fetch)
+        });
+        let outcomes = join_fetches(fetches).await;
+        let indices: Vec<usize> = outcomes.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        let err = outcomes[1].1.as_ref().unwrap_err().to_string();
+        assert!(
+            err.contains("Fetch task failed") && err.contains("panicked"),
+            "{err}"
+        );
+        assert!(outcomes[0]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("failed 0"));
     }
 }
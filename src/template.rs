@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::node::Node;
+use crate::xray::{check_supported, node_outbound};
+
+/// Marker replaced by the outbound of the selected node
+pub const NODE_OUTBOUND_MARKER: &str = "{{node_outbound}}";
+/// Protocol of a placeholder outbound replaced by the node's outbound
+pub const PLACEHOLDER_PROTOCOL: &str = "subman";
+
+/// Fill an xray config template with the node's outbound.
+///
+/// A template either contains the `{{node_outbound}}` marker (bare or quoted)
+/// where the outbound object goes, or is plain JSON with an outbound whose
+/// protocol is `subman`; that outbound is replaced and keeps its tag.
+pub fn render_template(template: &str, node: &Node) -> Result<Value> {
+    let outbound = node_outbound(node);
+
+    if template.contains(NODE_OUTBOUND_MARKER) {
+        let outbound = serde_json::to_string(&outbound)?;
+        let filled = template
+            .replace(&format!("\"{NODE_OUTBOUND_MARKER}\""), &outbound)
+            .replace(NODE_OUTBOUND_MARKER, &outbound);
+        return serde_json::from_str(&filled).map_err(|e| {
+            anyhow!("Template is not valid JSON after filling {NODE_OUTBOUND_MARKER}: {e}")
+        });
+    }
+
+    let mut config: Value =
+        serde_json::from_str(template).map_err(|e| anyhow!("Template is not valid JSON: {e}"))?;
+    let placeholder = config
+        .get_mut("outbounds")
+        .and_then(Value::as_array_mut)
+        .and_then(|outbounds| {
+            outbounds
+                .iter_mut()
+                .find(|o| o.get("protocol").and_then(Value::as_str) == Some(PLACEHOLDER_PROTOCOL))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Template has no {NODE_OUTBOUND_MARKER} marker or outbound with \"protocol\": \"{PLACEHOLDER_PROTOCOL}\""
+            )
+        })?;
+    let tag = placeholder.get("tag").cloned();
+    *placeholder = outbound;
+    if let Some(tag) = tag {
+        placeholder["tag"] = tag;
+    }
+    Ok(config)
+}
+
+/// Read the template file and fill it with the node's outbound
+pub fn template_config(node: &Node, template_path: &Path) -> Result<Value> {
+    check_supported(node)?;
+    let template = fs::read_to_string(template_path)
+        .map_err(|e| anyhow!("Failed to read template {}: {e}", template_path.display()))?;
+    render_template(&template, node).map_err(|e| anyhow!("{}: {e}", template_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node() -> Node {
+        Node::from_link("trojan://secret@a.test.com:443#A").unwrap()
+    }
+
+    #[test]
+    fn test_render_marker_template() {
+        let bare =
+            r#"{"outbounds": [{{node_outbound}}, {"tag": "direct", "protocol": "freedom"}]}"#;
+        let quoted = r#"{"outbounds": ["{{node_outbound}}"], "dns": {"servers": ["1.1.1.1"]}}"#;
+
+        let config = render_template(bare, &node()).unwrap();
+        assert_eq!(config["outbounds"][0]["protocol"], "trojan");
+        assert_eq!(config["outbounds"][1]["tag"], "direct");
+
+        let config = render_template(quoted, &node()).unwrap();
+        assert_eq!(
+            config["outbounds"][0]["settings"]["servers"][0]["password"],
+            "secret"
+        );
+        assert_eq!(config["dns"]["servers"][0], "1.1.1.1");
+
+        let err = render_template(r#"{"outbounds": [{{node_outbound}}"#, &node()).unwrap_err();
+        assert!(err.to_string().contains("not valid JSON"));
+    }
+
+    #[test]
+    fn test_render_placeholder_template() {
+        let template = r#"{
+            "inbounds": [{"port": 1080, "protocol": "socks"}],
+            "outbounds": [
+                {"tag": "proxy", "protocol": "subman"},
+                {"tag": "direct", "protocol": "freedom"}
+            ]
+        }"#;
+        let config = render_template(template, &node()).unwrap();
+        assert_eq!(config["outbounds"][0]["tag"], "proxy");
+        assert_eq!(config["outbounds"][0]["protocol"], "trojan");
+        assert_eq!(config["inbounds"][0]["port"], 1080);
+
+        let err = render_template(r#"{"outbounds": []}"#, &node()).unwrap_err();
+        assert!(err.to_string().contains(NODE_OUTBOUND_MARKER));
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+
+use crate::node::{Node, ProtocolSettings, Security, ShareUrl, TlsSettings};
+
+/// Parse a trojan://password@host:port?params#name link into a Node
+pub fn from_link(link: &str) -> Result<Node> {
+    let url = ShareUrl::parse(link, "trojan")?;
+
+    // Trojan runs over TLS unless the link explicitly says otherwise
+    let mut use_tls = true;
+    let mut tls = TlsSettings::default();
+    let transport = url.transport();
+
+    for (key, value) in url.params {
+        match key.as_str() {
+            "security" => use_tls = value != "none",
+            "sni" | "peer" => tls.sni = value,
+            "fp" => tls.fingerprint = value,
+            "alpn" => tls.alpn = value,
+            "allowInsecure" => tls.allow_insecure = value == "1" || value == "true",
+            _ => {}
+        }
+    }
+
+    let mut node = Node::new(
+        url.name,
+        url.address,
+        url.port,
+        ProtocolSettings::Trojan { password: url.user },
+    );
+    node.transport = transport;
+    node.security = if use_tls {
+        Security::Tls(tls)
+    } else {
+        Security::None
+    };
+    Ok(node)
+}
+
+/// Encode a trojan node as a trojan:// link
+pub fn to_link(node: &Node) -> String {
+    let mut params = match &node.security {
+        Security::Tls(tls) => tls.params(),
+        _ => vec![("security".to_string(), "none".to_string())],
+    };
+    params.extend(node.transport.params());
+
+    ShareUrl {
+        user: node.credential().to_string(),
+        address: node.address.clone(),
+        port: node.port,
+        name: node.name.clone(),
+        params,
+    }
+    .to_link("trojan")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trojan_link() {
+        let link = "trojan://p%40ss@trojan.test.com:443?sni=cdn.test.com&type=ws&path=%2Ftrojan&host=cdn.test.com#JP%20Trojan";
+
+        let node = from_link(link).unwrap();
+        assert_eq!(node.name, "JP Trojan");
+        assert_eq!(node.address, "trojan.test.com");
+        assert_eq!(node.port, 443);
+        assert_eq!(node.credential(), "p@ss");
+        assert_eq!(node.transport.network, "ws");
+        assert_eq!(node.transport.path, "/trojan");
+        let Security::Tls(tls) = &node.security else {
+            panic!("expected tls security");
+        };
+        assert_eq!(tls.sni, "cdn.test.com");
+    }
+}
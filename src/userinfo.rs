@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Traffic quota and expiry reported in a `subscription-userinfo` response header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct UserInfo {
+    /// Uploaded bytes
+    pub upload: u64,
+    /// Downloaded bytes
+    pub download: u64,
+    /// Traffic quota in bytes, 0 when unlimited
+    pub total: u64,
+    /// Unix timestamp of expiry, None when the plan never expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire: Option<u64>,
+}
+
+impl UserInfo {
+    /// Parse `upload=...; download=...; total=...; expire=...`, ignoring unknown keys
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut info = UserInfo::default();
+        let mut found = false;
+
+        for pair in header.split(';') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            // Some providers send floats such as 1.2e10
+            let Some(value) = value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .or_else(|| value.trim().parse::<f64>().ok().map(|v| v as u64))
+            else {
+                continue;
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "upload" => info.upload = value,
+                "download" => info.download = value,
+                "total" => info.total = value,
+                "expire" => info.expire = (value > 0).then_some(value),
+                _ => continue,
+            }
+            found = true;
+        }
+
+        found.then_some(info)
+    }
+
+    /// Uploaded plus downloaded bytes
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+
+    /// Remaining share of the quota in percent, None when unlimited
+    pub fn remaining_percent(&self) -> Option<u64> {
+        if self.total == 0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.used());
+        Some((remaining as u128 * 100 / self.total as u128) as u64)
+    }
+
+    /// Whole days until expiry (negative once expired), None when the plan never expires
+    pub fn days_left(&self, now: u64) -> Option<i64> {
+        self.expire
+            .map(|expire| (expire as i64 - now as i64).div_euclid(SECONDS_PER_DAY))
+    }
+
+    /// Short description such as "12.0 GB / 100.0 GB, 30 days left"
+    pub fn summary(&self, now: u64) -> String {
+        let traffic = if self.total == 0 {
+            format!("{} used", format_bytes(self.used()))
+        } else {
+            format!(
+                "{} / {}",
+                format_bytes(self.used()),
+                format_bytes(self.total)
+            )
+        };
+        match self.days_left(now) {
+            Some(days) if days < 0 => format!("{traffic}, expired"),
+            Some(days) => format!("{traffic}, {days} days left"),
+            None => traffic,
+        }
+    }
+
+    /// Warnings when the remaining quota or days until expiry fall under the thresholds
+    pub fn warnings(&self, now: u64, quota_percent: u64, expiry_days: u64) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(percent) = self.remaining_percent() {
+            if percent < quota_percent {
+                warnings.push(format!("{percent}% quota left"));
+            }
+        }
+        match self.days_left(now) {
+            Some(days) if days < 0 => warnings.push("expired".to_string()),
+            Some(days) if days < expiry_days as i64 => {
+                warnings.push(format!("expires in {days} days"))
+            }
+            _ => {}
+        }
+        warnings
+    }
+}
+
+/// Format a byte count with binary units, e.g. "1.5 GB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn test_parse_userinfo() {
+        let header = format!(
+            "upload={}; download={}; total={}; expire=1700864000",
+            GB,
+            44 * GB,
+            50 * GB
+        );
+        let info = UserInfo::parse(&header).unwrap();
+        assert_eq!(info.used(), 45 * GB);
+        assert_eq!(info.remaining_percent(), Some(10));
+        assert_eq!(info.expire, Some(1700864000));
+
+        let now = 1700864000 - 3 * 86400;
+        assert_eq!(info.days_left(now), Some(3));
+        assert_eq!(info.summary(now), "45.0 GB / 50.0 GB, 3 days left");
+        assert_eq!(
+            info.warnings(now, 20, 7),
+            vec!["10% quota left", "expires in 3 days"]
+        );
+        assert!(info.warnings(now, 5, 2).is_empty());
+
+        let unlimited = UserInfo::parse("upload=0; download=2048; total=0; expire=0").unwrap();
+        assert_eq!(unlimited.remaining_percent(), None);
+        assert_eq!(unlimited.expire, None);
+        assert_eq!(unlimited.summary(now), "2.0 KB used");
+
+        assert!(UserInfo::parse("garbage").is_none());
+    }
+}
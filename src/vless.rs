@@ -0,0 +1,136 @@
+use anyhow::Result;
+
+use crate::node::{Node, ProtocolSettings, RealitySettings, Security, ShareUrl, TlsSettings};
+
+/// Parse a vless://uuid@host:port?params#name link into a Node
+pub fn from_link(link: &str) -> Result<Node> {
+    let url = ShareUrl::parse(link, "vless")?;
+
+    let mut flow = String::new();
+    let mut encryption = String::new();
+    let mut security = String::new();
+    let mut tls = TlsSettings::default();
+    let mut reality = RealitySettings::default();
+    let transport = url.transport();
+
+    for (key, value) in url.params {
+        match key.as_str() {
+            "flow" => flow = value,
+            "encryption" => encryption = value,
+            "security" => security = value,
+            "sni" => {
+                tls.sni = value.clone();
+                reality.sni = value;
+            }
+            "fp" => {
+                tls.fingerprint = value.clone();
+                reality.fingerprint = value;
+            }
+            "alpn" => tls.alpn = value,
+            "allowInsecure" => tls.allow_insecure = value == "1" || value == "true",
+            "pbk" => reality.public_key = value,
+            "sid" => reality.short_id = value,
+            "spx" => reality.spider_x = value,
+            _ => {}
+        }
+    }
+
+    let mut node = Node::new(
+        url.name,
+        url.address,
+        url.port,
+        ProtocolSettings::Vless {
+            id: url.user,
+            flow,
+            encryption,
+        },
+    );
+    node.transport = transport;
+    node.security = match security.as_str() {
+        "tls" => Security::Tls(tls),
+        "reality" => Security::Reality(reality),
+        _ => Security::None,
+    };
+    Ok(node)
+}
+
+/// Encode a vless node as a vless:// link
+pub fn to_link(node: &Node) -> String {
+    let (id, flow, encryption) = match &node.settings {
+        ProtocolSettings::Vless {
+            id,
+            flow,
+            encryption,
+        } => (id.as_str(), flow.as_str(), encryption.as_str()),
+        _ => ("", "", ""),
+    };
+    let encryption = if encryption.is_empty() {
+        "none"
+    } else {
+        encryption
+    };
+    let mut params = vec![("encryption".to_string(), encryption.to_string())];
+    if !flow.is_empty() {
+        params.push(("flow".to_string(), flow.to_string()));
+    }
+    match &node.security {
+        Security::None => {}
+        Security::Tls(tls) => {
+            params.push(("security".to_string(), "tls".to_string()));
+            params.extend(tls.params());
+        }
+        Security::Reality(reality) => {
+            params.push(("security".to_string(), "reality".to_string()));
+            for (key, value) in [
+                ("sni", &reality.sni),
+                ("fp", &reality.fingerprint),
+                ("pbk", &reality.public_key),
+                ("sid", &reality.short_id),
+                ("spx", &reality.spider_x),
+            ] {
+                if !value.is_empty() {
+                    params.push((key.to_string(), value.clone()));
+                }
+            }
+        }
+    }
+    params.extend(node.transport.params());
+
+    ShareUrl {
+        user: id.to_string(),
+        address: node.address.clone(),
+        port: node.port,
+        name: node.name.clone(),
+        params,
+    }
+    .to_link("vless")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vless_reality_link() {
+        let link = "vless://b831381d-6324-4d53-ad4f-8cda48b30811@1.2.3.4:443?encryption=none&flow=xtls-rprx-vision&security=reality&sni=www.microsoft.com&fp=chrome&pbk=SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc&sid=6ba85179e30d4fc2&spx=%2F&type=tcp&headerType=none#HK%20Reality";
+
+        let node = from_link(link).unwrap();
+        assert_eq!(node.name, "HK Reality");
+        assert_eq!(node.address, "1.2.3.4");
+        assert_eq!(node.port, 443);
+        assert_eq!(node.credential(), "b831381d-6324-4d53-ad4f-8cda48b30811");
+        assert!(matches!(
+            &node.settings,
+            ProtocolSettings::Vless { flow, .. } if flow == "xtls-rprx-vision"
+        ));
+        let Security::Reality(reality) = &node.security else {
+            panic!("expected reality security");
+        };
+        assert_eq!(
+            reality.public_key,
+            "SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc"
+        );
+        assert_eq!(reality.short_id, "6ba85179e30d4fc2");
+        assert_eq!(reality.spider_x, "/");
+    }
+}
@@ -2,25 +2,10 @@ use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine};
 use serde::{Deserialize, Serialize};
 
-/// Latency test result status
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum LatencyStatus {
-    /// Not tested yet
-    #[default]
-    NotTested,
-    /// Test timed out
-    TimedOut,
-    /// Test succeeded with latency in ms
-    Success(u64),
-}
+use crate::node::{decode_base64, Node, ProtocolSettings, Security, TlsSettings, Transport};
 
-impl LatencyStatus {
-    pub fn is_tested(&self) -> bool {
-        !matches!(self, LatencyStatus::NotTested)
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// vmess share link payload (the base64-encoded JSON inside vmess://)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VmessNode {
     #[serde(default)]
     pub v: String,
@@ -50,34 +35,6 @@ pub struct VmessNode {
     pub alpn: String,
     #[serde(default)]
     pub fp: String,
-    // Runtime fields for latency
-    #[serde(skip)]
-    pub http_latency: LatencyStatus,
-    #[serde(skip)]
-    pub tcp_latency: LatencyStatus,
-}
-
-impl Default for VmessNode {
-    fn default() -> Self {
-        Self {
-            v: String::new(),
-            ps: String::new(),
-            add: String::new(),
-            port: serde_json::Value::Null,
-            id: String::new(),
-            aid: serde_json::Value::Null,
-            net: String::new(),
-            type_field: String::new(),
-            host: String::new(),
-            path: String::new(),
-            tls: String::new(),
-            sni: String::new(),
-            alpn: String::new(),
-            fp: String::new(),
-            http_latency: LatencyStatus::NotTested,
-            tcp_latency: LatencyStatus::NotTested,
-        }
-    }
 }
 
 impl VmessNode {
@@ -88,14 +45,7 @@ impl VmessNode {
             return Err(anyhow!("Invalid vmess link: must start with vmess://"));
         }
 
-        let encoded = &link[8..];
-        // Try standard base64 first, then URL-safe
-        let decoded = general_purpose::STANDARD
-            .decode(encoded)
-            .or_else(|_| general_purpose::URL_SAFE.decode(encoded))
-            .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(encoded))
-            .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(encoded))
-            .map_err(|e| anyhow!("Base64 decode error: {e}"))?;
+        let decoded = decode_base64(&link[8..])?;
 
         let json_str =
             String::from_utf8(decoded).map_err(|e| anyhow!("UTF-8 decode error: {e}"))?;
@@ -106,6 +56,12 @@ impl VmessNode {
         Ok(node)
     }
 
+    /// Encode as a vmess:// link (base64 of the JSON payload)
+    pub fn to_link(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        format!("vmess://{}", general_purpose::STANDARD.encode(json))
+    }
+
     /// Get the port as u16
     pub fn get_port(&self) -> u16 {
         match &self.port {
@@ -123,14 +79,94 @@ impl VmessNode {
             _ => 0,
         }
     }
+}
+
+impl From<VmessNode> for Node {
+    fn from(vmess: VmessNode) -> Self {
+        let port = vmess.get_port();
+        let alter_id = vmess.get_aid();
+
+        // v2rayN links carry the gRPC service name in the path field
+        let service_name = if vmess.net == "grpc" {
+            vmess.path.clone()
+        } else {
+            String::new()
+        };
+
+        let security = if vmess.tls == "tls" {
+            Security::Tls(TlsSettings {
+                sni: vmess.sni,
+                alpn: vmess.alpn,
+                fingerprint: vmess.fp,
+                allow_insecure: false,
+            })
+        } else {
+            Security::None
+        };
 
-    /// Get display name (ps field or address:port if ps is empty)
-    pub fn display_name(&self) -> String {
-        if self.ps.is_empty() {
-            format!("{}:{}", self.add, self.get_port())
+        let mut node = Node::new(
+            vmess.ps,
+            vmess.add,
+            port,
+            ProtocolSettings::Vmess {
+                id: vmess.id,
+                alter_id,
+            },
+        );
+        node.transport = Transport {
+            network: vmess.net,
+            header_type: vmess.type_field,
+            host: vmess.host,
+            path: vmess.path,
+            service_name,
+        };
+        node.security = security;
+        node
+    }
+}
+
+impl From<&Node> for VmessNode {
+    fn from(node: &Node) -> Self {
+        let (id, alter_id) = match &node.settings {
+            ProtocolSettings::Vmess { id, alter_id } => (id.clone(), *alter_id),
+            _ => (String::new(), 0),
+        };
+        let t = &node.transport;
+        let net = if t.network.is_empty() {
+            "tcp"
+        } else {
+            &t.network
+        };
+        // v2rayN links carry the gRPC service name in the path field
+        let path = if net == "grpc" {
+            &t.service_name
         } else {
-            self.ps.clone()
+            &t.path
+        };
+        let mut vmess = VmessNode {
+            v: "2".to_string(),
+            ps: node.name.clone(),
+            add: node.address.clone(),
+            port: node.port.to_string().into(),
+            id,
+            aid: alter_id.to_string().into(),
+            net: net.to_string(),
+            type_field: if t.header_type.is_empty() {
+                "none".to_string()
+            } else {
+                t.header_type.clone()
+            },
+            host: t.host.clone(),
+            path: path.clone(),
+            ..Default::default()
+        };
+        if let Security::Tls(tls) = &node.security {
+            vmess.tls = "tls".to_string();
+            vmess.sni = tls.sni.clone();
+            vmess.alpn = tls.alpn.clone();
+            vmess.fp = tls.fingerprint.clone();
         }
+        vmess
     }
 }
 
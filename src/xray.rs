@@ -1,11 +1,11 @@
 use anyhow::{anyhow, Result};
-use serde_json::json;
+use serde_json::{json, Value};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::thread;
-use std::time::Duration;
 
-use crate::vmess::VmessNode;
+use crate::node::{Node, Protocol, ProtocolSettings, Security};
+use crate::service::ServiceController;
 
 /// Default xray config path
 pub const DEFAULT_XRAY_CONFIG_PATH: &str = "/opt/homebrew/etc/xray/config.json";
@@ -15,104 +15,248 @@ pub const DEFAULT_SOCKS_PORT: u16 = 1080;
 /// Active node info extracted from xray config
 #[derive(Debug, Clone)]
 pub struct ActiveNodeInfo {
+    /// Outbound protocol name (vmess, vless, trojan, shadowsocks)
+    pub protocol: String,
     pub address: String,
     pub port: u16,
-    pub user_id: String,
+    /// User id (vmess/vless) or password (trojan/shadowsocks)
+    pub credential: String,
 }
 
-/// Read the current xray config and extract the active node info
-pub fn read_active_node(config_path: &str) -> Option<ActiveNodeInfo> {
+/// Outbound with the given tag, or the first outbound without a tag
+fn find_outbound<'a>(config: &'a Value, tag: Option<&str>) -> Option<&'a Value> {
+    let outbounds = config.get("outbounds")?.as_array()?;
+    match tag {
+        Some(tag) => outbounds
+            .iter()
+            .find(|o| o.get("tag").and_then(Value::as_str) == Some(tag)),
+        None => outbounds.first(),
+    }
+}
+
+/// Read the current xray config and extract the active node info from the
+/// outbound tagged `tag` (the first outbound when None)
+pub fn read_active_node(config_path: &str, tag: Option<&str>) -> Option<ActiveNodeInfo> {
     let content = fs::read_to_string(config_path).ok()?;
-    let config: serde_json::Value = serde_json::from_str(&content).ok()?;
-
-    // Navigate to outbounds[0].settings.vnext[0]
-    let vnext = config
-        .get("outbounds")?
-        .get(0)?
-        .get("settings")?
-        .get("vnext")?
-        .get(0)?;
-
-    let address = vnext.get("address")?.as_str()?.to_string();
-    let port = vnext.get("port")?.as_u64()? as u16;
-    let user_id = vnext
-        .get("users")?
-        .get(0)?
-        .get("id")?
-        .as_str()?
-        .to_string();
+    let config: Value = serde_json::from_str(&content).ok()?;
+
+    let outbound = find_outbound(&config, tag)?;
+    let protocol = outbound.get("protocol")?.as_str()?.to_string();
+    let settings = outbound.get("settings")?;
+
+    // vmess/vless: settings.vnext[0].users[0].id, trojan/shadowsocks: settings.servers[0].password
+    let (server, credential) = match settings.get("vnext").and_then(|v| v.get(0)) {
+        Some(vnext) => (vnext, vnext.get("users")?.get(0)?.get("id")?),
+        None => {
+            let server = settings.get("servers")?.get(0)?;
+            (server, server.get("password")?)
+        }
+    };
 
     Some(ActiveNodeInfo {
-        address,
-        port,
-        user_id,
+        protocol,
+        address: server.get("address")?.as_str()?.to_string(),
+        port: server.get("port")?.as_u64()? as u16,
+        credential: credential.as_str()?.to_string(),
     })
 }
 
 /// Find the index of the active node in the nodes list
-pub fn find_active_node_index(nodes: &[VmessNode], active: &ActiveNodeInfo) -> Option<usize> {
+pub fn find_active_node_index(nodes: &[Node], active: &ActiveNodeInfo) -> Option<usize> {
     nodes.iter().position(|node| {
-        node.add == active.address && node.get_port() == active.port && node.id == active.user_id
+        node.protocol().as_str() == active.protocol
+            && node.address == active.address
+            && node.port == active.port
+            && node.credential() == active.credential
     })
 }
 
-/// Generate xray config JSON for a vmess node
-pub fn generate_config(node: &VmessNode, socks_port: u16) -> serde_json::Value {
-    let port = node.get_port();
-    let aid = node.get_aid();
-
-    // Build stream settings based on network type
-    let mut stream_settings = json!({
-        "network": if node.net.is_empty() { "tcp" } else { &node.net }
-    });
+/// Build streamSettings from the node's transport and security layers
+fn stream_settings(node: &Node) -> serde_json::Value {
+    let t = &node.transport;
+    let net = match t.network.as_str() {
+        "" => "tcp",
+        "h2" => "http",
+        other => other,
+    };
+    let mut stream_settings = json!({ "network": net });
+
+    match net {
+        "ws" | "httpupgrade" => {
+            let mut settings = json!({});
+            if !t.path.is_empty() {
+                settings["path"] = json!(t.path);
+            }
+            if !t.host.is_empty() {
+                if net == "ws" {
+                    settings["headers"] = json!({ "Host": t.host });
+                } else {
+                    settings["host"] = json!(t.host);
+                }
+            }
+            let key = if net == "ws" {
+                "wsSettings"
+            } else {
+                "httpupgradeSettings"
+            };
+            stream_settings[key] = settings;
+        }
+        "grpc" => {
+            stream_settings["grpcSettings"] = json!({ "serviceName": t.service_name });
+        }
+        "http" => {
+            let mut settings = json!({});
+            if !t.path.is_empty() {
+                settings["path"] = json!(t.path);
+            }
+            if !t.host.is_empty() {
+                settings["host"] = json!(t.host.split(',').collect::<Vec<_>>());
+            }
+            stream_settings["httpSettings"] = settings;
+        }
+        "tcp" if t.header_type == "http" => {
+            stream_settings["tcpSettings"] = json!({
+                "header": {
+                    "type": "http",
+                    "request": {
+                        "path": [if t.path.is_empty() { "/" } else { &t.path }],
+                        "headers": {
+                            "Host": [if t.host.is_empty() { &node.address } else { &t.host }]
+                        }
+                    }
+                }
+            });
+        }
+        _ => {}
+    }
 
-    // Add WebSocket settings if network is ws
-    if node.net == "ws" {
-        let mut ws_settings = json!({});
-        if !node.path.is_empty() {
-            ws_settings["path"] = json!(node.path);
+    match &node.security {
+        Security::None => {}
+        Security::Tls(tls) => {
+            let mut tls_settings = json!({});
+            if !tls.sni.is_empty() {
+                tls_settings["serverName"] = json!(tls.sni);
+            } else if !t.host.is_empty() {
+                tls_settings["serverName"] = json!(t.host);
+            }
+            if !tls.alpn.is_empty() {
+                tls_settings["alpn"] = json!(tls.alpn.split(',').collect::<Vec<_>>());
+            }
+            if !tls.fingerprint.is_empty() {
+                tls_settings["fingerprint"] = json!(tls.fingerprint);
+            }
+            if tls.allow_insecure {
+                tls_settings["allowInsecure"] = json!(true);
+            }
+            stream_settings["security"] = json!("tls");
+            stream_settings["tlsSettings"] = tls_settings;
         }
-        if !node.host.is_empty() {
-            ws_settings["headers"] = json!({
-                "Host": node.host
+        Security::Reality(reality) => {
+            stream_settings["security"] = json!("reality");
+            stream_settings["realitySettings"] = json!({
+                "serverName": reality.sni,
+                "fingerprint": if reality.fingerprint.is_empty() { "chrome" } else { &reality.fingerprint },
+                "publicKey": reality.public_key,
+                "shortId": reality.short_id,
+                "spiderX": reality.spider_x
             });
         }
-        stream_settings["wsSettings"] = ws_settings;
     }
 
-    // Add TCP settings if type is http
-    if node.net == "tcp" && node.type_field == "http" {
-        stream_settings["tcpSettings"] = json!({
-            "header": {
-                "type": "http",
-                "request": {
-                    "path": [if node.path.is_empty() { "/" } else { &node.path }],
-                    "headers": {
-                        "Host": [if node.host.is_empty() { &node.add } else { &node.host }]
-                    }
+    stream_settings
+}
+
+/// Build the protocol-specific outbound settings
+fn protocol_settings(node: &Node) -> serde_json::Value {
+    match &node.settings {
+        ProtocolSettings::Vmess { id, alter_id } => json!({
+            "vnext": [
+                {
+                    "address": node.address,
+                    "port": node.port,
+                    "users": [
+                        {
+                            "id": id,
+                            "alterId": alter_id,
+                            "security": "auto"
+                        }
+                    ]
                 }
+            ]
+        }),
+        ProtocolSettings::Vless {
+            id,
+            flow,
+            encryption,
+        } => {
+            let mut user = json!({
+                "id": id,
+                "encryption": if encryption.is_empty() { "none" } else { encryption }
+            });
+            if !flow.is_empty() {
+                user["flow"] = json!(flow);
             }
-        });
+            json!({
+                "vnext": [
+                    {
+                        "address": node.address,
+                        "port": node.port,
+                        "users": [user]
+                    }
+                ]
+            })
+        }
+        ProtocolSettings::Trojan { password } => json!({
+            "servers": [
+                {
+                    "address": node.address,
+                    "port": node.port,
+                    "password": password
+                }
+            ]
+        }),
+        ProtocolSettings::Shadowsocks {
+            method, password, ..
+        } => json!({
+            "servers": [
+                {
+                    "address": node.address,
+                    "port": node.port,
+                    "method": method,
+                    "password": password
+                }
+            ]
+        }),
     }
+}
 
-    // Add TLS settings
-    if node.tls == "tls" {
-        stream_settings["security"] = json!("tls");
-        let mut tls_settings = json!({});
-        if !node.sni.is_empty() {
-            tls_settings["serverName"] = json!(node.sni);
-        } else if !node.host.is_empty() {
-            tls_settings["serverName"] = json!(node.host);
-        }
-        if !node.alpn.is_empty() {
-            tls_settings["alpn"] = json!(node.alpn.split(',').collect::<Vec<_>>());
-        }
-        if !node.fp.is_empty() {
-            tls_settings["fingerprint"] = json!(node.fp);
-        }
-        stream_settings["tlsSettings"] = tls_settings;
+/// Fail for nodes xray cannot connect to: shadowsocks nodes that need a
+/// SIP003 plugin, which xray cannot run
+pub fn check_supported(node: &Node) -> Result<()> {
+    match &node.settings {
+        ProtocolSettings::Shadowsocks { plugin, .. } if !plugin.is_empty() => Err(anyhow!(
+            "{} needs the shadowsocks plugin \"{plugin}\", which xray cannot run",
+            node.display_name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Build the outbound for a node
+pub fn node_outbound(node: &Node) -> serde_json::Value {
+    let mut outbound = json!({
+        "protocol": node.protocol().as_str(),
+        "settings": protocol_settings(node)
+    });
+    // Shadowsocks runs directly over TCP and needs no stream settings
+    if node.protocol() != Protocol::Shadowsocks {
+        outbound["streamSettings"] = stream_settings(node);
     }
+    outbound
+}
 
+/// Generate xray config JSON for a node
+pub fn generate_config(node: &Node, socks_port: u16) -> serde_json::Value {
     // Build the full config
     json!({
         "log": {
@@ -128,127 +272,191 @@ pub fn generate_config(node: &VmessNode, socks_port: u16) -> serde_json::Value {
                 }
             }
         ],
-        "outbounds": [
-            {
-                "protocol": "vmess",
-                "settings": {
-                    "vnext": [
-                        {
-                            "address": node.add,
-                            "port": port,
-                            "users": [
-                                {
-                                    "id": node.id,
-                                    "alterId": aid,
-                                    "security": "auto"
-                                }
-                            ]
-                        }
-                    ]
-                },
-                "streamSettings": stream_settings
-            }
-        ]
+        "outbounds": [node_outbound(node)]
     })
 }
 
-/// Save xray config to the specified path
-pub fn save_config_with_path(node: &VmessNode, config_path: &str) -> Result<()> {
-    let config = generate_config(node, DEFAULT_SOCKS_PORT);
-    let config_str = serde_json::to_string_pretty(&config)?;
-
-    std::fs::write(config_path, config_str)
-        .map_err(|e| anyhow!("Failed to write config to {config_path}: {e}"))?;
-
+/// Point the outbound tagged `tag` of an existing xray config at the node,
+/// leaving every other section untouched. Only `protocol`, `settings` and
+/// `streamSettings` are replaced; other keys of the outbound (e.g. `mux`,
+/// `sendThrough`, `proxySettings`) and `streamSettings.sockopt` are kept.
+pub fn patch_config(config: &mut Value, node: &Node, tag: &str) -> Result<()> {
+    let outbound = config
+        .get_mut("outbounds")
+        .and_then(Value::as_array_mut)
+        .and_then(|outbounds| {
+            outbounds
+                .iter_mut()
+                .find(|o| o.get("tag").and_then(Value::as_str) == Some(tag))
+        })
+        .ok_or_else(|| anyhow!("No outbound tagged \"{tag}\" in the xray config"))?;
+    let Some(outbound) = outbound.as_object_mut() else {
+        return Err(anyhow!("Outbound tagged \"{tag}\" is not an object"));
+    };
+    let sockopt = outbound
+        .get("streamSettings")
+        .and_then(|s| s.get("sockopt"))
+        .cloned();
+    let Value::Object(patched) = node_outbound(node) else {
+        unreachable!("node_outbound builds an object");
+    };
+    outbound.remove("streamSettings");
+    outbound.extend(patched);
+    if let Some(sockopt) = sockopt {
+        let stream = outbound
+            .entry("streamSettings")
+            .or_insert_with(|| json!({}));
+        stream["sockopt"] = sockopt;
+    }
     Ok(())
 }
 
-/// Save xray config to a custom path (for latency testing)
-pub fn save_config_to_path(node: &VmessNode, path: &str, socks_port: u16) -> Result<()> {
-    let config = generate_config(node, socks_port);
-    let config_str = serde_json::to_string_pretty(&config)?;
-
-    std::fs::write(path, config_str)
-        .map_err(|e| anyhow!("Failed to write config to {path}: {e}"))?;
-
-    Ok(())
+/// Build the xray config for a node: patch the outbound tagged `patch_tag`
+/// in the existing file, or generate a new config when None
+pub fn build_config(node: &Node, config_path: &str, patch_tag: Option<&str>) -> Result<Value> {
+    check_supported(node)?;
+    let Some(tag) = patch_tag else {
+        return Ok(generate_config(node, DEFAULT_SOCKS_PORT));
+    };
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| anyhow!("Failed to read {config_path}: {e}"))?;
+    let mut config: Value = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {config_path}: {e}"))?;
+    patch_config(&mut config, node, tag).map_err(|e| anyhow!("{e} ({config_path})"))?;
+    Ok(config)
 }
 
-/// Get xray process ID
-fn get_xray_pid() -> Option<u32> {
-    let output = Command::new("pgrep").arg("xray").output().ok()?;
+/// `xray run -test` command checking a config file
+fn test_command(path: &Path) -> Command {
+    let mut command = Command::new("xray");
+    command.args(["run", "-test", "-c"]).arg(path);
+    command
+}
 
-    if !output.status.success() {
-        return None;
+/// Check a config file with `xray run -test`
+pub fn test_config(path: &Path) -> Result<()> {
+    let output = test_command(path)
+        .output()
+        .map_err(|e| anyhow!("Failed to run xray -test: {e}"))?;
+    if output.status.success() {
+        return Ok(());
     }
-
+    // xray prints the reason on the last line
     let stdout = String::from_utf8_lossy(&output.stdout);
-    // Get the first PID if there are multiple
-    stdout.lines().next()?.trim().parse().ok()
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .chain(stdout.lines())
+        .rfind(|line| !line.trim().is_empty())
+        .unwrap_or("no output")
+        .trim()
+        .to_string();
+    Err(anyhow!("xray -test failed: {reason}"))
 }
 
-/// Restart result containing PIDs
-pub struct RestartResult {
-    pub old_pid: u32,
-    pub new_pid: u32,
+/// Temporary file next to `path`, so renaming it stays on the same filesystem.
+/// It keeps a `.json` suffix since xray picks the config format by extension.
+fn temp_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{stem}.subman-tmp.json"))
 }
 
-/// Restart xray service by sending SIGHUP signal
-pub fn restart_xray_service() -> Result<RestartResult> {
-    // Get current PID
-    let old_pid = get_xray_pid().ok_or_else(|| anyhow!("xray process not found"))?;
+/// Give `temp` the permissions and, where allowed, the owner of `original`
+fn copy_file_mode(original: &Path, temp: &Path) -> Result<()> {
+    let Ok(meta) = fs::metadata(original) else {
+        return Ok(());
+    };
+    fs::set_permissions(temp, meta.permissions())
+        .map_err(|e| anyhow!("Failed to set permissions of {}: {e}", temp.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Only root can hand the file to another owner; keep ours otherwise
+        let _ = std::os::unix::fs::chown(temp, Some(meta.uid()), Some(meta.gid()));
+    }
+    Ok(())
+}
 
-    // Send SIGHUP to reload config
-    let output = Command::new("kill")
-        .args(["-HUP", &old_pid.to_string()])
-        .output()
-        .map_err(|e| anyhow!("Failed to send HUP signal: {e}"))?;
+/// Write the config to a temporary file, validate it with `check` and rename
+/// it over `path`. Returns the previous contents of `path`, if any.
+fn install_config(
+    config: &Value,
+    path: &Path,
+    check: impl FnOnce(&Path) -> Result<()>,
+) -> Result<Option<Vec<u8>>> {
+    let temp = temp_path(path);
+    fs::write(&temp, serde_json::to_string_pretty(config)?)
+        .map_err(|e| anyhow!("Failed to write {}: {e}", temp.display()))?;
+    if let Err(e) = check(&temp).and_then(|_| copy_file_mode(path, &temp)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    let previous = fs::read(path).ok();
+    fs::rename(&temp, path).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        anyhow!("Failed to replace {}: {e}", path.display())
+    })?;
+    Ok(previous)
+}
+
+/// Atomically put previous contents back in place
+fn restore_config(path: &Path, previous: &[u8]) -> Result<()> {
+    let temp = temp_path(path);
+    fs::write(&temp, previous).map_err(|e| anyhow!("Failed to restore {}: {e}", path.display()))?;
+    copy_file_mode(path, &temp)?;
+    fs::rename(&temp, path).map_err(|e| anyhow!("Failed to restore {}: {e}", path.display()))
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
+/// Validate the config with `xray run -test`, move it into place and restart
+/// xray with `service`. If that fails, the previous config is restored and
+/// xray restarted again. Returns the service's description of the restart.
+pub fn apply_config(
+    config: &Value,
+    config_path: &str,
+    service: &dyn ServiceController,
+) -> Result<String> {
+    let path = Path::new(config_path);
+    let previous = install_config(config, path, test_config)
+        .map_err(|e| anyhow!("Config not applied, {config_path} is unchanged: {e}"))?;
+
+    let error = match service.restart() {
+        Ok(summary) => return Ok(summary),
+        Err(e) => e,
+    };
+    let Some(previous) = previous else {
         return Err(anyhow!(
-            "Failed to send HUP to xray (PID {old_pid}): {stderr}"
+            "xray failed to reload the new config: {error}. There was no previous config to restore"
         ));
+    };
+    restore_config(path, &previous)
+        .map_err(|e| anyhow!("xray failed to reload the new config: {error}. {e}"))?;
+    match service.restart() {
+        Ok(summary) => Err(anyhow!(
+            "xray failed to reload the new config: {error}. Restored the previous config ({summary})"
+        )),
+        Err(e) => Err(anyhow!(
+            "xray failed to reload the new config: {error}. Restored the previous config, but reloading it failed too: {e}"
+        )),
     }
+}
 
-    // Poll every 500ms for up to 3 seconds to check if PID changed (restart success)
-    let max_attempts = 6; // 6 * 500ms = 3000ms
-    for attempt in 0..max_attempts {
-        thread::sleep(Duration::from_millis(500));
+/// Save xray config to a custom path (for latency testing)
+pub fn save_config_to_path(node: &Node, path: &str, socks_port: u16) -> Result<()> {
+    check_supported(node)?;
+    let config = generate_config(node, socks_port);
+    let config_str = serde_json::to_string_pretty(&config)?;
 
-        match get_xray_pid() {
-            Some(new_pid) => {
-                if new_pid != old_pid {
-                    // PID changed - restart success
-                    return Ok(RestartResult { old_pid, new_pid });
-                }
-                // PID unchanged, keep waiting unless this is the last attempt
-                if attempt == max_attempts - 1 {
-                    return Err(anyhow!(
-                        "xray PID unchanged ({old_pid}) after 3 seconds - restart failed"
-                    ));
-                }
-            }
-            None => {
-                // Process not found, wait a bit more unless this is the last attempt
-                if attempt == max_attempts - 1 {
-                    return Err(anyhow!(
-                        "xray process (PID {old_pid}) disappeared after HUP signal"
-                    ));
-                }
-            }
-        }
-    }
+    std::fs::write(path, config_str)
+        .map_err(|e| anyhow!("Failed to write config to {path}: {e}"))?;
 
-    Err(anyhow!(
-        "Timeout waiting for xray (PID {old_pid}) to restart"
-    ))
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::vmess::LatencyStatus;
+    use crate::vmess::VmessNode;
+    use crate::{trojan, vless};
 
     #[test]
     fn test_generate_config() {
@@ -267,12 +475,184 @@ mod tests {
             sni: "".to_string(),
             alpn: "".to_string(),
             fp: "".to_string(),
-            http_latency: LatencyStatus::NotTested,
-            tcp_latency: LatencyStatus::NotTested,
         };
 
-        let config = generate_config(&node, 1080);
+        let config = generate_config(&Node::from(node), 1080);
         assert!(config["inbounds"][0]["port"] == 1080);
         assert!(config["outbounds"][0]["protocol"] == "vmess");
     }
+
+    #[test]
+    fn test_generate_vless_reality_config() {
+        let node = vless::from_link(
+            "vless://uuid@1.2.3.4:443?encryption=none&flow=xtls-rprx-vision&security=reality&sni=www.microsoft.com&fp=chrome&pbk=pubkey&sid=abcd&type=tcp#Reality",
+        )
+        .unwrap();
+
+        let config = generate_config(&node, 1080);
+        let outbound = &config["outbounds"][0];
+        assert!(outbound["protocol"] == "vless");
+        assert!(outbound["settings"]["vnext"][0]["users"][0]["flow"] == "xtls-rprx-vision");
+        assert!(outbound["streamSettings"]["security"] == "reality");
+        assert!(outbound["streamSettings"]["realitySettings"]["publicKey"] == "pubkey");
+        assert!(outbound["streamSettings"]["realitySettings"]["shortId"] == "abcd");
+    }
+
+    #[test]
+    fn test_generate_trojan_config() {
+        let node = trojan::from_link(
+            "trojan://secret@trojan.test.com:443?sni=cdn.test.com&type=ws&path=%2Fws#Trojan",
+        )
+        .unwrap();
+
+        let config = generate_config(&node, 1080);
+        let outbound = &config["outbounds"][0];
+        assert!(outbound["protocol"] == "trojan");
+        assert!(outbound["settings"]["servers"][0]["password"] == "secret");
+        assert!(outbound["streamSettings"]["network"] == "ws");
+        assert!(outbound["streamSettings"]["tlsSettings"]["serverName"] == "cdn.test.com");
+    }
+
+    #[test]
+    fn test_generate_shadowsocks_config() {
+        let node = Node::new(
+            String::new(),
+            "ss.test.com".to_string(),
+            8388,
+            ProtocolSettings::Shadowsocks {
+                method: "2022-blake3-aes-128-gcm".to_string(),
+                password: "key".to_string(),
+                plugin: String::new(),
+                plugin_opts: String::new(),
+            },
+        );
+
+        let config = generate_config(&node, 1080);
+        let server = &config["outbounds"][0]["settings"]["servers"][0];
+        assert!(config["outbounds"][0]["protocol"] == "shadowsocks");
+        assert!(server["method"] == "2022-blake3-aes-128-gcm");
+        assert!(server["port"] == 8388);
+        assert!(check_supported(&node).is_ok());
+
+        let plugin = Node::from_link(
+            "ss://YWVzLTI1Ni1nY206c2VjcmV0@s.test.com:8388/?plugin=obfs-local%3Bobfs%3Dhttp#Obfs",
+        )
+        .unwrap();
+        let err = check_supported(&plugin).unwrap_err().to_string();
+        assert!(err.contains("obfs-local"), "{err}");
+        assert!(build_config(&plugin, "/nonexistent", None).is_err());
+        assert!(save_config_to_path(&plugin, "/nonexistent", 1080).is_err());
+    }
+
+    #[test]
+    fn test_find_active_node_index() {
+        let nodes = vec![
+            trojan::from_link("trojan://secret@a.test.com:443#A").unwrap(),
+            vless::from_link("vless://secret@a.test.com:443#B").unwrap(),
+        ];
+        let active = ActiveNodeInfo {
+            protocol: "vless".to_string(),
+            address: "a.test.com".to_string(),
+            port: 443,
+            credential: "secret".to_string(),
+        };
+        assert_eq!(find_active_node_index(&nodes, &active), Some(1));
+    }
+
+    #[test]
+    fn test_install_config() {
+        let dir = std::env::temp_dir().join(format!("subman-xray-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, "old").unwrap();
+
+        let rejected = install_config(&json!({ "new": 1 }), &path, |_| Err(anyhow!("bad")));
+        assert!(rejected.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!temp_path(&path).exists());
+
+        let previous = install_config(&json!({ "new": 1 }), &path, |temp| {
+            assert!(fs::read_to_string(temp).unwrap().contains("\"new\""));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(previous.as_deref(), Some(b"old".as_slice()));
+        assert!(fs::read_to_string(&path).unwrap().contains("\"new\""));
+
+        restore_config(&path, b"old").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+            install_config(&json!({ "new": 2 }), &path, |_| Ok(())).unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_test_command() {
+        let temp = temp_path(Path::new("/etc/xray/config.json"));
+        assert_eq!(temp, Path::new("/etc/xray/.config.subman-tmp.json"));
+
+        let command = test_command(&temp);
+        assert_eq!(command.get_program(), "xray");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            ["run", "-test", "-c", "/etc/xray/.config.subman-tmp.json"]
+        );
+    }
+
+    #[test]
+    fn test_patch_config() {
+        let node = trojan::from_link("trojan://secret@b.test.com:443#B").unwrap();
+        let mut config = json!({
+            "log": { "loglevel": "info" },
+            "inbounds": [{ "port": 1081, "protocol": "http" }],
+            "outbounds": [
+                { "tag": "direct", "protocol": "freedom" },
+                {
+                    "tag": "proxy",
+                    "protocol": "vmess",
+                    "settings": {},
+                    "mux": { "enabled": true, "concurrency": 8 },
+                    "sendThrough": "10.0.0.2",
+                    "streamSettings": { "network": "ws", "sockopt": { "mark": 255 } }
+                }
+            ],
+            "routing": { "rules": [{ "type": "field", "outboundTag": "direct" }] }
+        });
+        let original = config.clone();
+
+        patch_config(&mut config, &node, "proxy").unwrap();
+        let outbounds = config["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds[0], original["outbounds"][0]);
+        assert_eq!(outbounds[1]["tag"], "proxy");
+        assert_eq!(outbounds[1]["protocol"], "trojan");
+        assert_eq!(
+            outbounds[1]["settings"]["servers"][0]["address"],
+            "b.test.com"
+        );
+        for key in ["log", "inbounds", "routing"] {
+            assert_eq!(config[key], original[key]);
+        }
+        // User settings of the patched outbound survive
+        for key in ["mux", "sendThrough"] {
+            assert_eq!(outbounds[1][key], original["outbounds"][1][key]);
+        }
+        assert_eq!(outbounds[1]["streamSettings"]["sockopt"]["mark"], 255);
+        assert_eq!(outbounds[1]["streamSettings"]["network"], "tcp");
+        assert_eq!(outbounds[1]["streamSettings"]["security"], "tls");
+
+        let active = find_outbound(&config, Some("proxy")).unwrap();
+        assert_eq!(active["protocol"], "trojan");
+
+        let err = patch_config(&mut config, &node, "missing").unwrap_err();
+        assert!(err.to_string().contains("\"missing\""));
+    }
 }